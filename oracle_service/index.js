@@ -3,12 +3,19 @@ const express = require('express');
 const app = express();
 const port = 7666;
 
-app.get('/mock', (_, res) => {
+// A stub price source for local testing, shaped like a typical exchange ticker.
+// Register a feed with the path `data.price` and start the node with
+// `--oracle-endpoint <feed id>=http://localhost:7666/price`.
+app.get('/price', (_, res) => {
   console.log('was hit!');
 
-  const data = Buffer.from('2a', 'hex');
-  // console.log(data.toString('hex'));
-  res.status(200).send(data);
+  res.status(200).json({
+    status: 'ok',
+    data: {
+      symbol: 'BTC/USD',
+      price: '42.00',
+    },
+  });
 });
 
 app.listen(port, () => console.log(`Started the oracle service on port ${port}.`));
@@ -1,5 +1,6 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
+use codec::{Encode, Decode, Input};
 use primitives::crypto::KeyTypeId;
 
 pub const ORACLE: KeyTypeId = KeyTypeId(*b"orac");
@@ -9,3 +10,126 @@ pub type Balance = u128;
 
 /// An index to a block.
 pub type BlockNumber = u32;
+
+/// A fixed-point price of `value / 10^decimals`, e.g. `65000.12345678` is a value of `6500012345678` at 8 decimals.
+///
+/// Arithmetic never goes through floating point and always rounds down, so every node values a position the same.
+/// A price never has more than `MAX_DECIMALS` decimals, decoding one with more fails.
+#[derive(Default, Encode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Price {
+	value: u128,
+	decimals: u8,
+}
+
+impl Price {
+	/// The most decimals a price can have.
+	pub const MAX_DECIMALS: u8 = 18;
+
+	/// A price of `value / 10^decimals`. `None` if `decimals` exceeds `MAX_DECIMALS`.
+	pub fn new(value: u128, decimals: u8) -> Option<Self> {
+		if decimals > Self::MAX_DECIMALS {
+			return None;
+		}
+		Some(Price { value, decimals })
+	}
+
+	/// A price of `value` at the decimals of this price.
+	pub fn with_value(self, value: u128) -> Self {
+		Price { value, decimals: self.decimals }
+	}
+
+	/// The price in units of `10^-decimals`.
+	pub fn value(&self) -> u128 {
+		self.value
+	}
+
+	/// The number of decimals the value is scaled by.
+	pub fn decimals(&self) -> u8 {
+		self.decimals
+	}
+
+	/// The same price at `decimals` decimals, rounded down when decimals are dropped. `None` on overflow, or if
+	/// `decimals` exceeds `MAX_DECIMALS`.
+	pub fn rescale(self, decimals: u8) -> Option<Self> {
+		if decimals > Self::MAX_DECIMALS {
+			return None;
+		}
+		let value = if decimals >= self.decimals {
+			self.value.checked_mul(10u128.pow((decimals - self.decimals) as u32))?
+		} else {
+			self.value / 10u128.pow((self.decimals - decimals) as u32)
+		};
+		Some(Price { value, decimals })
+	}
+
+	/// Both prices at the larger of their decimals, so they can be compared or subtracted. `None` on overflow.
+	pub fn align(self, other: Self) -> Option<(Self, Self)> {
+		let decimals = self.decimals.max(other.decimals);
+		Some((self.rescale(decimals)?, other.rescale(decimals)?))
+	}
+
+	/// The value of `quantity` units at this price, rounded down and saturating at `u128::max_value()`.
+	pub fn saturating_mul_int(self, quantity: u128) -> u128 {
+		let scale = 10u128.pow(self.decimals as u32);
+		match quantity.checked_mul(self.value) {
+			Some(total) => total / scale,
+			// Dividing first loses less than one unit of `self.value`, only when the result is enormous anyway.
+			None => (quantity / scale).saturating_mul(self.value),
+		}
+	}
+}
+
+impl Decode for Price {
+	fn decode<I: Input>(input: &mut I) -> Result<Self, codec::Error> {
+		let value = u128::decode(input)?;
+		let decimals = u8::decode(input)?;
+		// Scaling by more decimals overflows, prices from outside the runtime must not get that far.
+		Price::new(value, decimals).ok_or_else(|| "Price has more than MAX_DECIMALS decimals".into())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn decoding_rejects_too_many_decimals() {
+		let price = Price::new(2905, 2).unwrap();
+		assert_eq!(Price::decode(&mut &price.encode()[..]).ok(), Some(price));
+
+		let encoded = (2905u128, Price::MAX_DECIMALS + 1).encode();
+		assert!(Price::decode(&mut &encoded[..]).is_err());
+		assert_eq!(Price::new(2905, Price::MAX_DECIMALS + 1), None);
+	}
+
+	#[test]
+	fn rescales_up_and_down() {
+		let price = Price::new(12_345, 2).unwrap();
+		assert_eq!(price.rescale(4), Price::new(1_234_500, 4));
+		// Dropping decimals rounds down.
+		assert_eq!(price.rescale(1), Price::new(1_234, 1));
+		assert_eq!(price.rescale(0), Price::new(123, 0));
+		assert_eq!(price.rescale(Price::MAX_DECIMALS + 1), None);
+		assert_eq!(Price::new(u128::max_value(), 0).unwrap().rescale(1), None);
+	}
+
+	#[test]
+	fn aligns_to_the_larger_decimals() {
+		let (a, b) = Price::new(15, 1).unwrap().align(Price::new(1_499, 3).unwrap()).unwrap();
+		assert_eq!((a, b), (Price::new(1_500, 3).unwrap(), Price::new(1_499, 3).unwrap()));
+		assert_eq!(Price::new(u128::max_value(), 0).unwrap().align(Price::new(1, 1).unwrap()), None);
+	}
+
+	#[test]
+	fn multiplies_by_quantity() {
+		// 3 units at 29.05, rounded down from 87.15.
+		assert_eq!(Price::new(2905, 2).unwrap().saturating_mul_int(3), 87);
+		assert_eq!(Price::new(2905, 2).unwrap().saturating_mul_int(0), 0);
+
+		// The product overflows, so the quantity is scaled down first.
+		let quantity = u128::max_value() / 100;
+		assert_eq!(Price::new(300, 2).unwrap().saturating_mul_int(quantity), quantity / 100 * 300);
+		assert_eq!(Price::new(u128::max_value(), 0).unwrap().saturating_mul_int(2), u128::max_value());
+	}
+}
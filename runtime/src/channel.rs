@@ -7,49 +7,196 @@
 //! 
 //! ### Public Functions
 //! 
-//! - `open_channel` - Opens a new channel saving the initial state.
+//! - `one_way_channel` - Opens a channel where only the sender posts collateral and value flows to the recipient.
+//! - `open_channel` - Opens a two-party channel saving the initial state, the counterparty must fund it.
+//! - `fund_channel` - The counterparty posts their collateral, opening a two-party channel.
+//! - `cancel_channel` - The opener of a two-party channel the counterparty never funded takes their collateral back.
+//! - `close_one_way_channel` - Starts closing a one-way channel, the channel enters a challenge window.
+//! - `close_channel` - Starts closing a two-party channel with a jointly signed balance state.
 //! - `settle_channel` - An agreed settlement of a channel, does not require a dispute.
 //! - `dispute_channel` - Begins a dispute on a channel's state, something went wrong in the channel.
-//! - `liquidate_channel` - Attempts a liquidation of a channel with a signed state proving that one party is undercollaterized.
+//! - `reclaim_channel` - The sender of an expired one-way channel starts a close without a signed state.
+//! - `finalize_channel` - Pays out a closing channel once its challenge window has passed.
+//! - `liquidate_channel` - Attempts a liquidation of a channel with a signed state proving that one party is undercollaterized, the channel enters a challenge window.
 //! - `collateralize_channel` - Inserts more collateral, does not require a signed state and should be verified by the other chain participant when signing new state.
 //! 
+//! ## Collateral
+//!
+//! Every channel holds its collateral in an escrow account derived from the channel id, see
+//! `Module::escrow_account`. Collateral is never pooled between channels, so the funds backing a channel can
+//! be audited by reading the free balance of its escrow.
+//! 
 //! ## Dependencies
 //! 
 //! This module depends on the Oracle module.
 
 use codec::{Encode, Decode};
 use rstd::prelude::*;
-use rstd::convert::{TryInto};
+use rstd::result;
 #[cfg(not(feature = "std"))]
 use rstd::alloc::borrow::ToOwned;
-use support::{decl_module, decl_storage, decl_event, ensure, StorageMap, StorageValue, dispatch::Result};
-use support::traits::{Currency, ReservableCurrency};
+use sr_primitives::{ModuleId, Perbill};
+use sr_primitives::traits::{AccountIdConversion, CheckedAdd, SaturatedConversion, Saturating, Zero};
+use support::{decl_module, decl_storage, decl_event, decl_error, ensure, StorageMap, StorageValue};
+use support::traits::{Currency, ExistenceRequirement, Get, Imbalance, WithdrawReason};
 use system::ensure_signed;
 
+use crate::oracle::{FeedId, OracleProvider};
+use tiedye_primitives::Price;
+
 use primitives::sr25519;
 use primitives::crypto::Public;
 use runtime_io::sr25519_verify;
 
+/// The module id the channel escrow accounts are derived from.
+const MODULE_ID: ModuleId = ModuleId(*b"tie/chnl");
+
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum ChannelKind {
+	/// Only the sender posts collateral and value only flows to the recipient.
+	OneWay,
+	/// Both participants post collateral and value flows either way.
+	TwoWay,
+}
+
+impl Default for ChannelKind {
+	fn default() -> Self {
+		ChannelKind::OneWay
+	}
+}
+
+/// The version of the `ChannelState` message this runtime accepts.
+pub const CHANNEL_STATE_VERSION: u8 = 5;
+
+/// The split of a channel's collateral between its participants.
+#[derive(Default, Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct ChannelBalances<Balance> {
+	sender: Balance,
+	recipient: Balance,
+}
+
+/// A synthetic position held between the participants of a two-party channel, valued against an oracle feed.
+#[derive(Default, Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Position<Balance> {
+	/// The oracle feed pricing the position's asset.
+	feed: FeedId,
+	/// Whether the sender holds the long side, the recipient always holds the other side.
+	sender_long: bool,
+	/// Units of the asset the position is for.
+	quantity: Balance,
+	/// The feed's price when the position was entered.
+	entry_price: Price,
+}
+
+/// A channel state as signed off-chain by the participants. Every close and dispute verifies signatures
+/// over the SCALE encoding of this message.
+#[derive(Default, Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct ChannelState<Hash, Balance> {
+	/// The message format, must equal `CHANNEL_STATE_VERSION`.
+	version: u8,
+	/// The genesis hash of the chain the state was signed for, so it cannot be replayed on another chain.
+	genesis_hash: Hash,
+	/// The channel the state was signed for, so it cannot be replayed on another channel.
+	channel_id: u32,
+	/// Increases with every state signed for the channel, a higher nonce replaces a lower one. Signed states
+	/// start at one, zero stands for the collateral as it was posted.
+	nonce: u64,
+	balances: ChannelBalances<Balance>,
+	/// The collateral each participant had posted when the state was signed, which `balances` split. Collateral
+	/// added later is credited on top to whoever added it.
+	collateral: ChannelBalances<Balance>,
+	/// The open synthetic position, if any. The balances are the participants' margin before profit and loss.
+	position: Option<Position<Balance>>,
+	/// Marks the state the participants agreed to settle on, only such a state can be used by `settle_channel`.
+	is_final: bool,
+}
+
+/// A state submitted to close a channel, waiting out its challenge window.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct PendingClose<AccountId, Balance, BlockNumber> {
+	/// The participant that started the close.
+	initiator: AccountId,
+	/// The nonce of the latest submitted state.
+	nonce: u64,
+	/// The latest submitted split of the channel's collateral.
+	balances: ChannelBalances<Balance>,
+	/// The account that liquidated the channel and its fee, paid on top of `balances`, if the close is a
+	/// liquidation.
+	liquidation: Option<(AccountId, Balance)>,
+	/// The block at which the challenge window ends and the channel can pay out.
+	closes_at: BlockNumber,
+}
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum ChannelStatus<AccountId, Balance, BlockNumber> {
+	/// A two-party channel waiting for the counterparty to post their collateral.
+	AwaitingFunds,
+	/// The channel is live and can be used off-chain.
+	Open,
+	/// A close was started, the counterparty may still answer with a newer state.
+	Closing(PendingClose<AccountId, Balance, BlockNumber>),
+}
+
+impl<AccountId, Balance, BlockNumber> Default for ChannelStatus<AccountId, Balance, BlockNumber> {
+	fn default() -> Self {
+		ChannelStatus::Open
+	}
+}
+
 #[derive(Default, Encode, Decode)]
 #[cfg_attr(feature = "std", derive(Debug))]
-pub struct Channel<AccountId, Balance, Moment> {
+pub struct Channel<AccountId, Balance, Moment, BlockNumber> {
+	kind: ChannelKind,
 	sender: AccountId,
 	signing_key: Vec<u8>,
 	recipient: AccountId,
+	/// The recipient's signing key, only set for two-party channels.
+	recipient_key: Vec<u8>,
 	start: Moment,
 	collateral: Balance,
+	/// The collateral posted by the recipient, always zero for one-way channels.
+	recipient_collateral: Balance,
+	/// The block after which the sender of a one-way channel may reclaim its collateral without a signed state.
+	expires_at: Option<BlockNumber>,
+	status: ChannelStatus<AccountId, Balance, BlockNumber>,
 }
 
 type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type ChannelOf<T> = Channel<<T as system::Trait>::AccountId, BalanceOf<T>, <T as timestamp::Trait>::Moment, <T as system::Trait>::BlockNumber>;
+type ChannelStateOf<T> = ChannelState<<T as system::Trait>::Hash, BalanceOf<T>>;
 
 pub trait Trait: system::Trait + timestamp::Trait {
-	type Currency: ReservableCurrency<Self::AccountId> + Currency<Self::AccountId>;
+	type Currency: Currency<Self::AccountId>;
 	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The number of blocks a closing channel waits for a newer state before it can pay out.
+	type ChallengePeriod: Get<Self::BlockNumber>;
+
+	/// The number of blocks the recipient of an expired one-way channel has to answer the sender reclaiming it.
+	type ExpiryGracePeriod: Get<Self::BlockNumber>;
+
+	/// The oracle that prices synthetic positions.
+	type Oracle: OracleProvider;
+
+	/// The share of a position's notional value each participant must keep as equity.
+	type MaintenanceMargin: Get<Perbill>;
+
+	/// The share of the liquidated participant's equity paid to whoever triggers the liquidation.
+	type LiquidationFee: Get<Perbill>;
+
+	/// The window, in milliseconds, of the time-weighted average price positions are valued at.
+	type PriceWindow: Get<u64>;
 }
 
 decl_storage! {
 	trait Store for Module<T: Trait> as ChannelStorage {
-		Channels get(channels): map u32 => Channel<T::AccountId, BalanceOf<T>, T::Moment>;
+		Channels get(channels): map u32 => ChannelOf<T>;
 
 		KeyRegistry get(key_registry): map T::AccountId => Vec<u8>;
 
@@ -59,22 +206,58 @@ decl_storage! {
 
 decl_module! {
 	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		type Error = Error;
+
 		fn deposit_event() = default;
 
+		/// The number of blocks a closing channel waits for a newer state.
+		const ChallengePeriod: T::BlockNumber = T::ChallengePeriod::get();
+
+		/// The number of blocks the recipient of an expired one-way channel has to answer a reclaim.
+		const ExpiryGracePeriod: T::BlockNumber = T::ExpiryGracePeriod::get();
+
+		/// The share of a position's notional value each participant must keep as equity.
+		const MaintenanceMargin: Perbill = T::MaintenanceMargin::get();
+
+		/// The share of the liquidated participant's equity paid to the liquidator.
+		const LiquidationFee: Perbill = T::LiquidationFee::get();
+
+		/// The window, in milliseconds, of the time-weighted average price positions are valued at.
+		const PriceWindow: u64 = T::PriceWindow::get();
+
 		// We insert the public key here, this way we make a distinction between the key being used for signing
 		// and the key for the account. This is so that the account can remain secure while the signing key may be
 		// delegated to a possible third party.
-		pub fn one_way_channel(origin, public_key: Vec<u8>, recipient: T::AccountId, collateral: BalanceOf<T>) -> Result {
+		//
+		// The sender may set an expiry, after which they can reclaim the collateral should the recipient never close.
+		pub fn one_way_channel(
+			origin,
+			public_key: Vec<u8>,
+			recipient: T::AccountId,
+			collateral: BalanceOf<T>,
+			expires_at: Option<T::BlockNumber>
+		) -> result::Result<(), Error> {
 			let sender = ensure_signed(origin)?;
 
-			T::Currency::reserve(&sender, collateral)?;
+			if let Some(expires_at) = expires_at {
+				ensure!(expires_at > <system::Module<T>>::block_number(), Error::ExpiryInPast);
+			}
+
+			// The collateral moves into the escrow of the id the channel is about to take.
+			T::Currency::transfer(&sender, &Self::escrow_account(<NextFreeId>::get()), collateral)
+				.map_err(|_| Error::CollateralTransferFailed)?;
 
 			let new_channel = Channel {
+				kind: ChannelKind::OneWay,
 				sender: sender.clone(),
 				signing_key: public_key.to_owned(),
 				recipient: recipient,
+				recipient_key: Vec::new(),
 				collateral: collateral,
+				recipient_collateral: Default::default(),
 				start: <timestamp::Module<T>>::now(),
+				expires_at,
+				status: ChannelStatus::Open,
 			};
 
 			let channel_id = Self::new_id();
@@ -87,60 +270,333 @@ decl_module! {
 			Ok(())
 		}
 
-		// Ideally we would put the channel_id into the message instead of two variables.
-		pub fn close_one_way_channel(origin, channel_id: u32, amount: Vec<u8>, signature: Vec<u8>) -> Result {
-			// There are two conditions for which a one-way channel could close:
-			//  - Sender is closing. Sender could be submitting an expired state so we must keep the channel in a
-			//    a dispute period for some length of time. During the dispute period, the recipient can submit a newer
-			//	  and valid state signed by both parties. 
-			//  - Recipient is closing. No matter who closes, we need a dispute period. The only case we will not need a 
-			//    dispute period is if it was a strict rule in the second-layer protocol that value could increment
-			//	  up and never decrease.
-			//
-			// For sake of simplicity we do not implement a dispute period here. We're still trying to get MVP shipped.
-			// We only allow recipient to submit, and the message must be signed by sender.
-			let recipient = ensure_signed(origin)?;
-
-			// let i = read_be_u128(&mut amount.as_slice());
-			// support::print(i as u64);
-			// let val: BalanceOf<T> = i.try_into().ok().unwrap();
-			// let val: BalanceOf<T> = amount.as_slice().try_into().ok().unwrap();
-			let uint = u128::decode(&mut amount.as_slice()).unwrap();
-			support::print(uint as u64);
-			let val: BalanceOf<T> = uint.try_into().ok().unwrap();
+		/// Opens a two-party channel with `counterparty`. The channel only becomes usable once the counterparty
+		/// posts their own collateral through `fund_channel`.
+		pub fn open_channel(origin, public_key: Vec<u8>, counterparty: T::AccountId, collateral: BalanceOf<T>) -> result::Result<(), Error> {
+			let sender = ensure_signed(origin)?;
+
+			ensure!(sender != counterparty, Error::SelfChannel);
+
+			T::Currency::transfer(&sender, &Self::escrow_account(<NextFreeId>::get()), collateral)
+				.map_err(|_| Error::CollateralTransferFailed)?;
+
+			let new_channel = Channel {
+				kind: ChannelKind::TwoWay,
+				sender: sender.clone(),
+				signing_key: public_key.to_owned(),
+				recipient: counterparty,
+				recipient_key: Vec::new(),
+				collateral: collateral,
+				recipient_collateral: Default::default(),
+				start: <timestamp::Module<T>>::now(),
+				expires_at: None,
+				status: ChannelStatus::AwaitingFunds,
+			};
+
+			let channel_id = Self::new_id();
+
+			<Channels<T>>::insert(channel_id, new_channel);
+			<KeyRegistry<T>>::insert(sender.clone(), public_key);
+
+			Self::deposit_event(RawEvent::NewChannel(channel_id, sender));
+
+			Ok(())
+		}
+
+		/// The counterparty of a two-party channel posts their collateral and signing key, opening the channel.
+		pub fn fund_channel(origin, channel_id: u32, public_key: Vec<u8>, collateral: BalanceOf<T>) -> result::Result<(), Error> {
+			let who = ensure_signed(origin)?;
+
+			ensure!(<Channels<T>>::exists(channel_id), Error::UnknownChannel);
+			let channel = Self::channels(channel_id);
+
+			ensure!(who == channel.recipient, Error::NotCounterparty);
+			ensure!(channel.status == ChannelStatus::AwaitingFunds, Error::AlreadyFunded);
 
+			T::Currency::transfer(&who, &Self::escrow_account(channel_id), collateral)
+				.map_err(|_| Error::CollateralTransferFailed)?;
 
+			<Channels<T>>::mutate(channel_id, |c| {
+				c.recipient_key = public_key.clone();
+				c.recipient_collateral = collateral;
+				c.status = ChannelStatus::Open;
+			});
+			<KeyRegistry<T>>::insert(who.clone(), public_key);
+
+			Self::deposit_event(RawEvent::ChannelFunded(channel_id, who, collateral));
+
+			Ok(())
+		}
+
+		/// The opener of a two-party channel takes their collateral back while the counterparty has not funded it
+		/// yet, removing the channel.
+		pub fn cancel_channel(origin, channel_id: u32) -> result::Result<(), Error> {
+			let who = ensure_signed(origin)?;
+
+			ensure!(<Channels<T>>::exists(channel_id), Error::UnknownChannel);
 			let channel = Self::channels(channel_id);
 
-			// Make sure the sender is the recipient of this simple channel.
-			ensure!(recipient == channel.recipient, "Sender is not the channel recipient");
+			ensure!(who == channel.sender, Error::NotSender);
+			ensure!(channel.status == ChannelStatus::AwaitingFunds, Error::AlreadyFunded);
 
-			// The off-chain logic should never allow this to possible, so the adjudication layer will throw it.
-			ensure!(channel.collateral >= val, "Submitted an impossible state");
+			Self::pay_out(channel_id, vec![(channel.sender.clone(), channel.collateral)])?;
 
-			// We may eventually want to change this to pay out the highest possible balance, in the case of something
-			// like a slashing occurring on this user. The recipient of this channel would then need to bring it up
-			// in the governance mechanism.
-			ensure!(T::Currency::reserved_balance(&channel.sender) >= val, "Submitted an impossible state");
+			<Channels<T>>::remove(channel_id);
 
-			// Now we need to make sure that the signature matches that of the sender. We can use the `is_signed`
-			// helper function defined below.
-			ensure!(Self::is_signed(channel.signing_key, amount, signature), "Invalid signature");
+			Self::deposit_event(RawEvent::ChannelCancelled(channel_id, who));
 
-			// We unreserve the collateral locked in this channel and transfer the amount signed by the sender
-			// to the recipient.
-			T::Currency::unreserve(&channel.sender, channel.collateral);
+			Ok(())
+		}
 
-			// Finally make the transfer and complete the channel.
-			let currency_transfer = T::Currency::transfer(&channel.sender, &channel.recipient, val);
-			match currency_transfer {
-				Err(_e) => support::print(_e),
-				Ok(_v) => {}
+		/// Adds collateral to a live channel without closing it. Only the participants can top up, and only the
+		/// sender of a one-way channel since its recipient never posts collateral. States signed before the top
+		/// up stay valid, the top up is paid out to whoever made it on top of their balance in the state.
+		pub fn collateralize_channel(origin, channel_id: u32, amount: BalanceOf<T>) -> result::Result<(), Error> {
+			let who = ensure_signed(origin)?;
+
+			ensure!(<Channels<T>>::exists(channel_id), Error::UnknownChannel);
+			let channel = Self::channels(channel_id);
+
+			let is_sender = who == channel.sender;
+			ensure!(is_sender || who == channel.recipient, Error::NotParticipant);
+			ensure!(is_sender || channel.kind == ChannelKind::TwoWay, Error::RecipientCannotCollateralize);
+			ensure!(channel.status == ChannelStatus::Open, Error::ChannelNotOpen);
+
+			T::Currency::transfer(&who, &Self::escrow_account(channel_id), amount)
+				.map_err(|_| Error::CollateralTransferFailed)?;
+
+			<Channels<T>>::mutate(channel_id, |c| if is_sender {
+				c.collateral += amount;
+			} else {
+				c.recipient_collateral += amount;
+			});
+
+			Self::deposit_event(RawEvent::ChannelCollateralized(channel_id, who, amount));
+
+			Ok(())
+		}
+
+		pub fn close_one_way_channel(origin, channel_id: u32, state: ChannelStateOf<T>, signature: Vec<u8>) -> result::Result<(), Error> {
+			// Either participant may start a close. The sender could be submitting an expired state, so the
+			// channel waits out a challenge window before paying out. During the window the counterparty can
+			// answer with a newer state through `dispute_channel`.
+			let who = ensure_signed(origin)?;
+
+			ensure!(<Channels<T>>::exists(channel_id), Error::UnknownChannel);
+			let channel = Self::channels(channel_id);
+
+			ensure!(channel.kind == ChannelKind::OneWay, Error::NotOneWay);
+
+			Self::start_close(channel_id, channel, who, state, vec![signature])
+		}
+
+		/// Starts closing a two-party channel with a balance state signed by both participants. The
+		/// signatures are ordered sender first, recipient second.
+		pub fn close_channel(origin, channel_id: u32, state: ChannelStateOf<T>, signatures: Vec<Vec<u8>>) -> result::Result<(), Error> {
+			let who = ensure_signed(origin)?;
+
+			ensure!(<Channels<T>>::exists(channel_id), Error::UnknownChannel);
+			let channel = Self::channels(channel_id);
+
+			ensure!(channel.kind == ChannelKind::TwoWay, Error::NotTwoWay);
+
+			Self::start_close(channel_id, channel, who, state, signatures)
+		}
+
+		/// Answers a close with a newer state, before the challenge window ends.
+		///
+		/// A one-way channel state only carries the sender's signature, a two-party channel state carries
+		/// both signatures. A two-party state is newer if it has a higher nonce, and either participant may answer
+		/// with one, including the one that started the close. The sender alone can sign any nonce of a one-way
+		/// channel, whose value only ever moves to the recipient, so there a newer state is one that pays the
+		/// recipient more, and only the participant that did not start the close may dispute.
+		pub fn dispute_channel(origin, channel_id: u32, state: ChannelStateOf<T>, signatures: Vec<Vec<u8>>) -> result::Result<(), Error> {
+			let who = ensure_signed(origin)?;
+
+			ensure!(<Channels<T>>::exists(channel_id), Error::UnknownChannel);
+			let channel = Self::channels(channel_id);
+
+			ensure!(who == channel.sender || who == channel.recipient, Error::NotParticipant);
+			let pending = match channel.status {
+				ChannelStatus::Closing(ref pending) => pending.clone(),
+				_ => return Err(Error::NotClosing),
+			};
+			ensure!(channel.kind == ChannelKind::TwoWay || who != pending.initiator, Error::OwnClose);
+			ensure!(<system::Module<T>>::block_number() < pending.closes_at, Error::ChallengePeriodEnded);
+
+			let balances = Self::verify_state(channel_id, &channel, &state, &signatures)?;
+			match channel.kind {
+				ChannelKind::OneWay => ensure!(balances.recipient > pending.balances.recipient, Error::StaleState),
+				ChannelKind::TwoWay => ensure!(state.nonce > pending.nonce, Error::StaleState),
 			}
 
+			// A newer state voids a liquidation of an older one.
+			<Channels<T>>::mutate(channel_id, |c| c.status = ChannelStatus::Closing(PendingClose {
+				nonce: state.nonce,
+				balances,
+				liquidation: None,
+				..pending
+			}));
+
+			Self::deposit_event(RawEvent::ChannelDisputed(channel_id, who));
+
+			Ok(())
+		}
+
+		/// Settles a channel on a final state the participants agreed on, skipping the challenge window.
+		///
+		/// A two-party channel needs both signatures, sender first. The recipient of a one-way channel never
+		/// holds a signing key, so only they may settle it and their transaction stands in for their signature.
+		pub fn settle_channel(origin, channel_id: u32, state: ChannelStateOf<T>, signatures: Vec<Vec<u8>>) -> result::Result<(), Error> {
+			let who = ensure_signed(origin)?;
+
+			ensure!(<Channels<T>>::exists(channel_id), Error::UnknownChannel);
+			let channel = Self::channels(channel_id);
+
+			ensure!(who == channel.sender || who == channel.recipient, Error::NotParticipant);
+			ensure!(
+				channel.kind == ChannelKind::TwoWay || who == channel.recipient,
+				Error::NotRecipient
+			);
+			match channel.status {
+				ChannelStatus::Open => {},
+				ChannelStatus::Closing(ref pending) => ensure!(state.nonce >= pending.nonce, Error::StaleState),
+				ChannelStatus::AwaitingFunds => return Err(Error::ChannelNotOpen),
+			}
+			ensure!(state.is_final, Error::StateNotFinal);
+
+			let balances = Self::verify_state(channel_id, &channel, &state, &signatures)?;
+
+			Self::pay_out(channel_id, vec![
+				(channel.sender.clone(), balances.sender),
+				(channel.recipient.clone(), balances.recipient),
+			])?;
+
+			<Channels<T>>::remove(channel_id);
+
+			Self::deposit_event(RawEvent::ChannelSettled(channel_id, balances.sender, balances.recipient));
+
+			Ok(())
+		}
+
+		/// The sender reclaims the collateral of an expired one-way channel. The sender may have delegated their
+		/// signing key, so no signed state is needed. The close pays the full collateral back to the sender, unless
+		/// the recipient answers with their latest signed state through `dispute_channel` within the grace period.
+		pub fn reclaim_channel(origin, channel_id: u32) -> result::Result<(), Error> {
+			let who = ensure_signed(origin)?;
+
+			ensure!(<Channels<T>>::exists(channel_id), Error::UnknownChannel);
+			let channel = Self::channels(channel_id);
+
+			ensure!(who == channel.sender, Error::NotSender);
+			ensure!(channel.kind == ChannelKind::OneWay, Error::NotOneWay);
+			ensure!(channel.status == ChannelStatus::Open, Error::ChannelNotOpen);
+
+			let now = <system::Module<T>>::block_number();
+			let expires_at = channel.expires_at.ok_or(Error::NoExpiry)?;
+			ensure!(now >= expires_at, Error::NotExpired);
+
+			let closes_at = now + T::ExpiryGracePeriod::get();
+			<Channels<T>>::mutate(channel_id, |c| c.status = ChannelStatus::Closing(PendingClose {
+				initiator: who.clone(),
+				nonce: 0,
+				balances: ChannelBalances { sender: channel.collateral, recipient: Zero::zero() },
+				liquidation: None,
+				closes_at,
+			}));
+
+			Self::deposit_event(RawEvent::ChannelClosing(channel_id, who, closes_at));
+
+			Ok(())
+		}
+
+		/// Pays out a closing channel once its challenge window has passed. Anyone may call this.
+		pub fn finalize_channel(origin, channel_id: u32) -> result::Result<(), Error> {
+			let _ = ensure_signed(origin)?;
+
+			ensure!(<Channels<T>>::exists(channel_id), Error::UnknownChannel);
+			let channel = Self::channels(channel_id);
+
+			let pending = match channel.status {
+				ChannelStatus::Closing(ref pending) => pending.clone(),
+				_ => return Err(Error::NotClosing),
+			};
+			ensure!(<system::Module<T>>::block_number() >= pending.closes_at, Error::ChallengePeriodActive);
+
+			let mut payouts = vec![
+				(channel.sender.clone(), pending.balances.sender),
+				(channel.recipient.clone(), pending.balances.recipient),
+			];
+			payouts.extend(pending.liquidation);
+			Self::pay_out(channel_id, payouts)?;
+
 			// Delete the channel.
 			<Channels<T>>::remove(channel_id);
 
+			Self::deposit_event(RawEvent::ChannelClosed(channel_id, pending.balances.sender, pending.balances.recipient));
+
+			Ok(())
+		}
+
+		/// Liquidates a two-party channel whose signed position leaves one participant below the maintenance
+		/// margin at the oracle's time-weighted average price over `PriceWindow`. Anyone may call this and is
+		/// paid the liquidation fee out of the liquidated participant's equity.
+		///
+		/// Any state the participants ever signed can be submitted, so the liquidation enters the challenge window
+		/// like a close. Either participant may answer with a newer state through `dispute_channel`, which voids
+		/// the liquidation, otherwise the channel pays out at the liquidated balances once the window passes.
+		pub fn liquidate_channel(origin, channel_id: u32, state: ChannelStateOf<T>, signatures: Vec<Vec<u8>>) -> result::Result<(), Error> {
+			let liquidator = ensure_signed(origin)?;
+
+			ensure!(<Channels<T>>::exists(channel_id), Error::UnknownChannel);
+			let channel = Self::channels(channel_id);
+
+			ensure!(channel.kind == ChannelKind::TwoWay, Error::NotTwoWay);
+			match channel.status {
+				ChannelStatus::Open => {},
+				// A close in progress already proves a state, an older one cannot be used to liquidate.
+				ChannelStatus::Closing(ref pending) => {
+					ensure!(pending.liquidation.is_none(), Error::AlreadyLiquidating);
+					ensure!(state.nonce >= pending.nonce, Error::StaleState);
+				},
+				ChannelStatus::AwaitingFunds => return Err(Error::ChannelNotOpen),
+			}
+
+			let margin = Self::verify_state(channel_id, &channel, &state, &signatures)?;
+
+			let position = state.position.ok_or(Error::NoPosition)?;
+			ensure!(!T::Oracle::is_halted(position.feed), Error::FeedHalted);
+			let price = T::Oracle::twap(position.feed, T::PriceWindow::get()).ok_or(Error::NoPrice)?;
+
+			let balances = Self::mark_to_market(&margin, &position, price)?;
+			let notional = price.saturating_mul_int(position.quantity.saturated_into::<u128>())
+				.saturated_into::<BalanceOf<T>>();
+			let requirement = T::MaintenanceMargin::get() * notional;
+
+			// The fee comes out of the liquidated participant's equity.
+			let (liquidated, payouts) = if balances.sender < requirement {
+				let fee = T::LiquidationFee::get() * balances.sender;
+				(&channel.sender, [balances.sender - fee, balances.recipient, fee])
+			} else if balances.recipient < requirement {
+				let fee = T::LiquidationFee::get() * balances.recipient;
+				(&channel.recipient, [balances.sender, balances.recipient - fee, fee])
+			} else {
+				return Err(Error::SufficientlyCollateralized);
+			};
+			let fee = payouts[2];
+
+			// The liquidator is no participant, so both participants may dispute.
+			let closes_at = <system::Module<T>>::block_number() + T::ChallengePeriod::get();
+			<Channels<T>>::mutate(channel_id, |c| c.status = ChannelStatus::Closing(PendingClose {
+				initiator: liquidator.clone(),
+				nonce: state.nonce,
+				balances: ChannelBalances { sender: payouts[0], recipient: payouts[1] },
+				liquidation: Some((liquidator.clone(), fee)),
+				closes_at,
+			}));
+
+			Self::deposit_event(RawEvent::ChannelLiquidated(channel_id, liquidated.clone(), liquidator, fee));
+
 			Ok(())
 		}
 	}
@@ -151,7 +607,164 @@ impl<T: Trait> Module<T> {
 		<NextFreeId>::mutate(|n| { let r = *n; *n += 1; r })
 	}
 
+	/// Verifies `state` and moves an open channel into its challenge window.
+	fn start_close(
+		channel_id: u32,
+		channel: ChannelOf<T>,
+		who: T::AccountId,
+		state: ChannelStateOf<T>,
+		signatures: Vec<Vec<u8>>,
+	) -> result::Result<(), Error> {
+		ensure!(who == channel.sender || who == channel.recipient, Error::NotParticipant);
+		ensure!(channel.status == ChannelStatus::Open, Error::ChannelNotOpen);
+
+		let balances = Self::verify_state(channel_id, &channel, &state, &signatures)?;
+
+		let closes_at = <system::Module<T>>::block_number() + T::ChallengePeriod::get();
+		<Channels<T>>::mutate(channel_id, |c| c.status = ChannelStatus::Closing(PendingClose {
+			initiator: who.clone(),
+			nonce: state.nonce,
+			balances,
+			liquidation: None,
+			closes_at,
+		}));
+
+		Self::deposit_event(RawEvent::ChannelClosing(channel_id, who, closes_at));
+
+		Ok(())
+	}
+
+	/// The account holding the collateral of `channel_id`. It is derived from the channel id and has no known
+	/// private key, only this module moves funds out of it.
+	pub fn escrow_account(channel_id: u32) -> T::AccountId {
+		MODULE_ID.into_sub_account(channel_id)
+	}
+
+	/// Pays the collateral held in the escrow of `channel_id` out to `payouts`.
+	///
+	/// Storage is not rolled back when a dispatch fails, so every check runs before any balance moves. The whole
+	/// payout is then withdrawn from the escrow in one step, the only operation that can fail, and credited to
+	/// the payees. Either everything is paid out or nothing changes.
+	///
+	/// A payout that would leave its payee below the existential deposit cannot be credited. It is burned rather
+	/// than handed to a payee the signed state did not assign it to, so dust never blocks a payout.
+	fn pay_out(channel_id: u32, payouts: Vec<(T::AccountId, BalanceOf<T>)>) -> result::Result<(), Error> {
+		let escrow = Self::escrow_account(channel_id);
+
+		let total = payouts.iter()
+			.try_fold(BalanceOf::<T>::zero(), |total, (_, amount)| total.checked_add(amount))
+			.ok_or(Error::PayoutOverflow)?;
+
+		// We may eventually want to change this to pay out the highest possible balance, in the case of something
+		// like a slashing occurring on this user. The recipient of this channel would then need to bring it up
+		// in the governance mechanism.
+		ensure!(T::Currency::free_balance(&escrow) >= total, Error::InsufficientEscrow);
+
+		let payouts = payouts.into_iter()
+			.filter(|(who, amount)| {
+				!amount.is_zero()
+					&& T::Currency::free_balance(who).saturating_add(*amount) >= T::Currency::minimum_balance()
+			})
+			.collect::<Vec<_>>();
+
+		let mut imbalance = T::Currency::withdraw(
+			&escrow,
+			total,
+			WithdrawReason::Transfer.into(),
+			ExistenceRequirement::AllowDeath,
+		).map_err(|_| Error::EscrowWithdrawFailed)?;
+
+		for (who, amount) in payouts {
+			let (payout, rest) = imbalance.split(amount);
+			T::Currency::resolve_creating(&who, payout);
+			imbalance = rest;
+		}
+		// Dropping what is left, the dust no payee could take, burns it.
+		drop(imbalance);
+
+		Ok(())
+	}
+
+	/// Settles the profit and loss of `position` at `price` into the participants' margin. The loser can never
+	/// lose more than the margin they hold in the channel.
+	fn mark_to_market(
+		balances: &ChannelBalances<BalanceOf<T>>,
+		position: &Position<BalanceOf<T>>,
+		price: Price,
+	) -> result::Result<ChannelBalances<BalanceOf<T>>, Error> {
+		let (price, entry_price) = price.align(position.entry_price).ok_or(Error::IncomparablePrice)?;
+		let (move_size, price_rose) = if price.value() >= entry_price.value() {
+			(price.value() - entry_price.value(), true)
+		} else {
+			(entry_price.value() - price.value(), false)
+		};
+		let pnl = price.with_value(move_size)
+			.saturating_mul_int(position.quantity.saturated_into::<u128>())
+			.saturated_into::<BalanceOf<T>>();
+
+		Ok(if price_rose == position.sender_long {
+			let pnl = pnl.min(balances.recipient);
+			ChannelBalances { sender: balances.sender + pnl, recipient: balances.recipient - pnl }
+		} else {
+			let pnl = pnl.min(balances.sender);
+			ChannelBalances { sender: balances.sender - pnl, recipient: balances.recipient + pnl }
+		})
+	}
+
+	/// Checks that `state` was signed for this channel on this chain and by the channel's participants, and
+	/// returns its balances with the collateral added since it was signed credited to whoever added it.
+	///
+	/// A one-way state only needs the sender's signature, a two-party state needs both, sender first. In
+	/// either case the balances must split exactly the collateral the state was signed against, which the
+	/// channel must hold.
+	fn verify_state(
+		channel_id: u32,
+		channel: &ChannelOf<T>,
+		state: &ChannelStateOf<T>,
+		signatures: &[Vec<u8>],
+	) -> result::Result<ChannelBalances<BalanceOf<T>>, Error> {
+		ensure!(state.version == CHANNEL_STATE_VERSION, Error::UnsupportedStateVersion);
+		ensure!(state.channel_id == channel_id, Error::WrongChannel);
+		ensure!(
+			state.genesis_hash == <system::Module<T>>::block_hash(T::BlockNumber::zero()),
+			Error::WrongChain
+		);
+
+		// The off-chain logic should never allow this to possible, so the adjudication layer will throw it.
+		let collateral = &state.collateral;
+		ensure!(
+			collateral.sender <= channel.collateral && collateral.recipient <= channel.recipient_collateral,
+			Error::ImpossibleState
+		);
+		let total = collateral.sender.checked_add(&collateral.recipient);
+		ensure!(state.balances.sender.checked_add(&state.balances.recipient) == total, Error::ImpossibleState);
+
+		let message = state.encode();
+		match channel.kind {
+			ChannelKind::OneWay => {
+				ensure!(signatures.len() == 1, Error::WrongSignatureCount);
+				ensure!(Self::is_signed(channel.signing_key.clone(), message, signatures[0].clone()), Error::InvalidSignature);
+			},
+			ChannelKind::TwoWay => {
+				ensure!(signatures.len() == 2, Error::WrongSignatureCount);
+				ensure!(Self::is_signed(channel.signing_key.clone(), message.clone(), signatures[0].clone()), Error::InvalidSignature);
+				ensure!(Self::is_signed(channel.recipient_key.clone(), message, signatures[1].clone()), Error::InvalidSignature);
+			},
+		}
+
+		// Neither sum overflows, both stay below the channel's collateral which the escrow holds.
+		Ok(ChannelBalances {
+			sender: state.balances.sender + (channel.collateral - collateral.sender),
+			recipient: state.balances.recipient + (channel.recipient_collateral - collateral.recipient),
+		})
+	}
+
 	fn is_signed(pub_key: Vec<u8>, msg: Vec<u8>, sig: Vec<u8>) -> bool {
+		// `from_slice` panics on a length mismatch, and both come straight from the extrinsic.
+		if pub_key.len() != 32 || sig.len() != 64 {
+			return false;
+		}
+
 		let s = sr25519::Signature::from_slice(sig.as_slice());
 		let p = sr25519::Public::from_slice(pub_key.as_slice());
 		sr25519_verify(&s, msg.as_slice(), &p)
@@ -163,9 +776,108 @@ impl<T: Trait> Module<T> {
 	// }
 }
 
+decl_error! {
+	/// Errors of the channel module.
+	pub enum Error {
+		/// No channel exists with the given id.
+		UnknownChannel,
+		/// The caller is not a participant of the channel.
+		NotParticipant,
+		/// Only the sender of the channel may do this.
+		NotSender,
+		/// Only the recipient of the channel may do this.
+		NotRecipient,
+		/// Only the counterparty of the two-party channel may fund it.
+		NotCounterparty,
+		/// A two-party channel needs two distinct participants.
+		SelfChannel,
+		/// The channel's counterparty already posted their collateral.
+		AlreadyFunded,
+		/// The recipient of a one-way channel never posts collateral.
+		RecipientCannotCollateralize,
+		/// The channel is not open.
+		ChannelNotOpen,
+		/// The call only applies to one-way channels.
+		NotOneWay,
+		/// The call only applies to two-party channels.
+		NotTwoWay,
+		/// The channel is not in its challenge window.
+		NotClosing,
+		/// The participant that started the close of a one-way channel cannot dispute it.
+		OwnClose,
+		/// The challenge window of the close has ended.
+		ChallengePeriodEnded,
+		/// The challenge window of the close has not ended yet.
+		ChallengePeriodActive,
+		/// The submitted state is not newer than the one already submitted.
+		StaleState,
+		/// Cooperative settlement needs a state marked final.
+		StateNotFinal,
+		/// The expiry of a new channel must be in the future.
+		ExpiryInPast,
+		/// The channel was opened without an expiry.
+		NoExpiry,
+		/// The channel has not expired yet.
+		NotExpired,
+		/// The state holds no position to liquidate.
+		NoPosition,
+		/// The oracle has no price covering the whole price window for the position's feed.
+		NoPrice,
+		/// The position's feed is halted, its prices cannot be acted on.
+		FeedHalted,
+		/// The position's entry price cannot be brought to the oracle price's decimals.
+		IncomparablePrice,
+		/// The channel is already being liquidated.
+		AlreadyLiquidating,
+		/// Neither participant is below the maintenance margin.
+		SufficientlyCollateralized,
+		/// The state was signed with a message format this runtime does not accept.
+		UnsupportedStateVersion,
+		/// The state was signed for another channel.
+		WrongChannel,
+		/// The state was signed for another chain.
+		WrongChain,
+		/// The state's balances do not add up to its collateral, or it claims more collateral than was posted.
+		ImpossibleState,
+		/// The state carries the wrong number of signatures for the channel.
+		WrongSignatureCount,
+		/// A signature does not match the participant's signing key.
+		InvalidSignature,
+		/// Moving collateral into the channel's escrow failed.
+		CollateralTransferFailed,
+		/// The channel's escrow holds less than the payout.
+		InsufficientEscrow,
+		/// The payouts add up to more than a balance can hold.
+		PayoutOverflow,
+		/// Withdrawing the payout from the channel's escrow failed.
+		EscrowWithdrawFailed,
+	}
+}
+
 decl_event!(
-	pub enum Event<T> where AccountId = <T as system::Trait>::AccountId {
+	pub enum Event<T> where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+		BlockNumber = <T as system::Trait>::BlockNumber,
+	{
 		NewChannel(u32, AccountId),
+		/// The counterparty posted their collateral and the two-party channel is open.
+		ChannelFunded(u32, AccountId, Balance),
+		/// The opener cancelled a two-party channel the counterparty never funded and took their collateral back.
+		ChannelCancelled(u32, AccountId),
+		/// A participant added collateral to a live channel.
+		ChannelCollateralized(u32, AccountId, Balance),
+		/// A participant started closing a channel, it can pay out at the given block.
+		ChannelClosing(u32, AccountId, BlockNumber),
+		/// The counterparty answered a close with a newer state.
+		ChannelDisputed(u32, AccountId),
+		/// A channel paid out the sender's and recipient's balances and was removed.
+		ChannelClosed(u32, Balance, Balance),
+		/// The participants settled a channel on an agreed state, paying out the sender's and recipient's balances.
+		ChannelSettled(u32, Balance, Balance),
+		/// An undercollateralized participant is being liquidated, the liquidator earns the given fee unless a
+		/// newer state is submitted within the challenge window.
+		ChannelLiquidated(u32, AccountId, AccountId, Balance),
 	}
 );
 
@@ -175,11 +887,11 @@ mod tests {
 	use super::*;
 
 	use runtime_io::with_externalities;
-	use primitives::{H256, Blake2Hasher};
-	use support::{impl_outer_origin, assert_ok, parameter_types};
-	use sr_primitives::{traits::{BlakeTwo256, IdentityLookup}, testing::Header};
+	use primitives::{H256, Blake2Hasher, Pair};
+	use support::{impl_outer_origin, assert_ok, assert_noop, parameter_types};
+	use sr_primitives::{traits::{BlakeTwo256, IdentityLookup, ConvertInto}, testing::Header, BuildStorage};
 	use sr_primitives::weights::Weight;
-	use sr_primitives::Perbill;
+	use std::cell::RefCell;
 
 	impl_outer_origin! {
 		pub enum Origin for Test {}
@@ -214,25 +926,528 @@ mod tests {
 		type AvailableBlockRatio = AvailableBlockRatio;
 		type Version = ();
 	}
+	thread_local! {
+		static EXISTENTIAL_DEPOSIT: RefCell<u64> = RefCell::new(0);
+	}
+	pub struct ExistentialDeposit;
+	impl Get<u64> for ExistentialDeposit {
+		fn get() -> u64 {
+			EXISTENTIAL_DEPOSIT.with(|v| *v.borrow())
+		}
+	}
+	parameter_types! {
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+		pub const TransactionBaseFee: u64 = 0;
+		pub const TransactionByteFee: u64 = 0;
+	}
+	impl balances::Trait for Test {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = ();
+		type TransactionPayment = ();
+		type DustRemoval = ();
+		type TransferPayment = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+		type TransactionBaseFee = TransactionBaseFee;
+		type TransactionByteFee = TransactionByteFee;
+		type WeightToFee = ConvertInto;
+	}
+	parameter_types! {
+		pub const MinimumPeriod: u64 = 1;
+	}
+	impl timestamp::Trait for Test {
+		type Moment = u64;
+		type OnTimestampSet = ();
+		type MinimumPeriod = MinimumPeriod;
+	}
+	parameter_types! {
+		pub const ChallengePeriod: u64 = 10;
+		pub const ExpiryGracePeriod: u64 = 5;
+	}
+	thread_local! {
+		static PRICE: RefCell<Option<Price>> = RefCell::new(None);
+		static HALTED: RefCell<bool> = RefCell::new(false);
+	}
+	pub struct MockOracle;
+	impl OracleProvider for MockOracle {
+		fn median(_feed: FeedId) -> Option<Price> {
+			PRICE.with(|p| *p.borrow())
+		}
+
+		fn latest(_feed: FeedId) -> Option<Price> {
+			PRICE.with(|p| *p.borrow())
+		}
+
+		fn twap(_feed: FeedId, _window: u64) -> Option<Price> {
+			PRICE.with(|p| *p.borrow())
+		}
+
+		fn is_halted(_feed: FeedId) -> bool {
+			HALTED.with(|h| *h.borrow())
+		}
+	}
+	parameter_types! {
+		pub const MaintenanceMargin: Perbill = Perbill::from_percent(10);
+		pub const LiquidationFee: Perbill = Perbill::from_percent(10);
+		pub const PriceWindow: u64 = 60_000;
+	}
 	impl Trait for Test {
 		type Event = ();
+		type Currency = Balances;
+		type ChallengePeriod = ChallengePeriod;
+		type ExpiryGracePeriod = ExpiryGracePeriod;
+		type Oracle = MockOracle;
+		type MaintenanceMargin = MaintenanceMargin;
+		type LiquidationFee = LiquidationFee;
+		type PriceWindow = PriceWindow;
 	}
 	type TemplateModule = Module<Test>;
+	type Balances = balances::Module<Test>;
+	type System = system::Module<Test>;
+
+	const SENDER: u64 = 1;
+	const RECIPIENT: u64 = 2;
 
 	// This function basically just builds a genesis storage key/value store according to
 	// our desired mockup.
 	fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
-		system::GenesisConfig::default().build_storage::<Test>().unwrap().into()
+		let mut t = system::GenesisConfig::default().build_storage::<Test>().unwrap();
+		balances::GenesisConfig::<Test> {
+			balances: vec![(SENDER, 1000), (RECIPIENT, 1000)],
+			vesting: vec![],
+		}.assimilate_storage(&mut t).unwrap();
+		t.into()
+	}
+
+	fn signing_pair() -> sr25519::Pair {
+		sr25519::Pair::from_seed(&[1u8; 32])
+	}
+
+	fn recipient_pair() -> sr25519::Pair {
+		sr25519::Pair::from_seed(&[2u8; 32])
+	}
+
+	/// A state signed against the collateral `channel_id` holds now.
+	fn channel_state(channel_id: u32, nonce: u64, sender: u64, recipient: u64) -> ChannelState<H256, u64> {
+		let channel = TemplateModule::channels(channel_id);
+		ChannelState {
+			version: CHANNEL_STATE_VERSION,
+			genesis_hash: System::block_hash(0),
+			channel_id,
+			nonce,
+			balances: ChannelBalances { sender, recipient },
+			collateral: ChannelBalances { sender: channel.collateral, recipient: channel.recipient_collateral },
+			position: None,
+			is_final: false,
+		}
+	}
+
+	/// A two-party channel state signed by both participants.
+	fn signed_balances(nonce: u64, sender: u64, recipient: u64) -> (ChannelState<H256, u64>, Vec<Vec<u8>>) {
+		sign_both(channel_state(0, nonce, sender, recipient))
+	}
+
+	fn sign_both(state: ChannelState<H256, u64>) -> (ChannelState<H256, u64>, Vec<Vec<u8>>) {
+		let message = state.encode();
+		let signatures = vec![
+			signing_pair().sign(&message).as_ref().to_vec(),
+			recipient_pair().sign(&message).as_ref().to_vec(),
+		];
+		(state, signatures)
+	}
+
+	fn open_two_way(collateral: u64, recipient_collateral: u64) -> u32 {
+		let public = signing_pair().public().as_ref().to_vec();
+		let recipient_public = recipient_pair().public().as_ref().to_vec();
+		let channel_id = <NextFreeId>::get();
+		assert_ok!(TemplateModule::open_channel(Origin::signed(SENDER), public, RECIPIENT, collateral));
+		assert_ok!(TemplateModule::fund_channel(Origin::signed(RECIPIENT), channel_id, recipient_public, recipient_collateral));
+		channel_id
+	}
+
+	/// A one-way state paying `amount` out of a collateral of 100 to the recipient, signed by the sender.
+	fn signed_amount(nonce: u64, amount: u64) -> (ChannelState<H256, u64>, Vec<u8>) {
+		let state = channel_state(0, nonce, 100 - amount, amount);
+		let signature = signing_pair().sign(&state.encode());
+		(state, signature.as_ref().to_vec())
+	}
+
+	fn open_one_way(collateral: u64) -> u32 {
+		open_expiring(collateral, None)
+	}
+
+	fn open_expiring(collateral: u64, expires_at: Option<u64>) -> u32 {
+		let channel_id = <NextFreeId>::get();
+		let public = signing_pair().public().as_ref().to_vec();
+		assert_ok!(TemplateModule::one_way_channel(Origin::signed(SENDER), public, RECIPIENT, collateral, expires_at));
+		channel_id
+	}
+
+	#[test]
+	fn close_waits_for_challenge_period() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			let channel_id = open_one_way(100);
+			let (amount, signature) = signed_amount(1, 40);
+
+			assert_ok!(TemplateModule::close_one_way_channel(Origin::signed(RECIPIENT), channel_id, amount, signature));
+			assert_noop!(TemplateModule::finalize_channel(Origin::signed(RECIPIENT), channel_id), Error::ChallengePeriodActive);
+
+			System::set_block_number(11);
+			assert_ok!(TemplateModule::finalize_channel(Origin::signed(RECIPIENT), channel_id));
+			assert_eq!(Balances::free_balance(&SENDER), 960);
+			assert_eq!(Balances::free_balance(&RECIPIENT), 1040);
+			assert!(!<Channels<Test>>::exists(channel_id));
+		});
+	}
+
+	#[test]
+	fn recipient_can_dispute_a_stale_close() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			let channel_id = open_one_way(100);
+			let (stale, stale_signature) = signed_amount(1, 10);
+			let (latest, latest_signature) = signed_amount(2, 70);
+
+			assert_ok!(TemplateModule::close_one_way_channel(Origin::signed(SENDER), channel_id, stale, stale_signature));
+			assert_ok!(TemplateModule::dispute_channel(Origin::signed(RECIPIENT), channel_id, latest, vec![latest_signature]));
+
+			System::set_block_number(11);
+			assert_ok!(TemplateModule::finalize_channel(Origin::signed(SENDER), channel_id));
+			assert_eq!(Balances::free_balance(&RECIPIENT), 1070);
+		});
+	}
+
+	#[test]
+	fn sender_cannot_dispute_with_a_state_paying_less() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			let channel_id = open_one_way(100);
+			let (latest, latest_signature) = signed_amount(5, 70);
+			// The sender can sign any nonce on their own.
+			let (forged, forged_signature) = signed_amount(6, 0);
+
+			assert_ok!(TemplateModule::close_one_way_channel(Origin::signed(RECIPIENT), channel_id, latest, latest_signature));
+			assert_noop!(
+				TemplateModule::dispute_channel(Origin::signed(SENDER), channel_id, forged, vec![forged_signature]),
+				Error::StaleState
+			);
+
+			System::set_block_number(11);
+			assert_ok!(TemplateModule::finalize_channel(Origin::signed(RECIPIENT), channel_id));
+			assert_eq!(Balances::free_balance(&RECIPIENT), 1070);
+		});
+	}
+
+	#[test]
+	fn two_way_channel_splits_combined_collateral() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			let channel_id = open_two_way(100, 50);
+			assert_eq!(Balances::free_balance(&TemplateModule::escrow_account(channel_id)), 150);
+
+			// The sender won 30 from the recipient over the life of the channel.
+			let (stale, stale_signatures) = signed_balances(1, 90, 60);
+			let (latest, latest_signatures) = signed_balances(2, 130, 20);
+			assert_noop!(
+				TemplateModule::close_channel(Origin::signed(SENDER), channel_id, signed_balances(3, 100, 100).0, latest_signatures.clone()),
+				Error::ImpossibleState
+			);
+
+			assert_ok!(TemplateModule::close_channel(Origin::signed(RECIPIENT), channel_id, stale, stale_signatures));
+			assert_ok!(TemplateModule::dispute_channel(Origin::signed(SENDER), channel_id, latest, latest_signatures));
+
+			System::set_block_number(11);
+			assert_ok!(TemplateModule::finalize_channel(Origin::signed(SENDER), channel_id));
+			assert_eq!(Balances::free_balance(&SENDER), 1030);
+			assert_eq!(Balances::free_balance(&RECIPIENT), 970);
+			assert_eq!(Balances::free_balance(&TemplateModule::escrow_account(channel_id)), 0);
+		});
+	}
+
+	#[test]
+	fn initiator_can_answer_a_dispute_with_a_newer_state() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			let channel_id = open_two_way(100, 50);
+			let (closed, closed_signatures) = signed_balances(5, 90, 60);
+			let (disputed, disputed_signatures) = signed_balances(7, 130, 20);
+			let (latest, latest_signatures) = signed_balances(9, 60, 90);
+
+			assert_ok!(TemplateModule::close_channel(Origin::signed(RECIPIENT), channel_id, closed, closed_signatures));
+			assert_ok!(TemplateModule::dispute_channel(Origin::signed(SENDER), channel_id, disputed, disputed_signatures.clone()));
+			assert_noop!(
+				TemplateModule::dispute_channel(Origin::signed(RECIPIENT), channel_id, signed_balances(7, 130, 20).0, disputed_signatures),
+				Error::StaleState
+			);
+			assert_ok!(TemplateModule::dispute_channel(Origin::signed(RECIPIENT), channel_id, latest, latest_signatures));
+
+			System::set_block_number(11);
+			assert_ok!(TemplateModule::finalize_channel(Origin::signed(SENDER), channel_id));
+			assert_eq!(Balances::free_balance(&SENDER), 960);
+			assert_eq!(Balances::free_balance(&RECIPIENT), 1040);
+		});
+	}
+
+	#[test]
+	fn opener_cancels_unfunded_channel() {
+		with_externalities(&mut new_test_ext(), || {
+			let public = signing_pair().public().as_ref().to_vec();
+			let channel_id = <NextFreeId>::get();
+			assert_ok!(TemplateModule::open_channel(Origin::signed(SENDER), public, RECIPIENT, 100));
+
+			assert_noop!(TemplateModule::cancel_channel(Origin::signed(RECIPIENT), channel_id), Error::NotSender);
+			assert_ok!(TemplateModule::cancel_channel(Origin::signed(SENDER), channel_id));
+
+			assert_eq!(Balances::free_balance(&SENDER), 1000);
+			assert_eq!(Balances::free_balance(&TemplateModule::escrow_account(channel_id)), 0);
+			assert!(!<Channels<Test>>::exists(channel_id));
+
+			// A funded channel can only be closed.
+			let channel_id = open_two_way(100, 50);
+			assert_noop!(TemplateModule::cancel_channel(Origin::signed(SENDER), channel_id), Error::AlreadyFunded);
+		});
 	}
 
 	#[test]
-	fn it_works_for_default_value() {
+	fn state_for_another_channel_is_rejected() {
 		with_externalities(&mut new_test_ext(), || {
-			// Just a dummy test for the dummy funtion `do_something`
-			// calling the `do_something` function with a value 42
-			assert_ok!(TemplateModule::do_something(Origin::signed(1), 42));
-			// asserting that the stored value is equal to what we stored
-			assert_eq!(TemplateModule::something(), Some(42));
+			let channel_id = open_one_way(100);
+			let _ = open_one_way(100);
+
+			// A state signed for the first channel cannot close the second, even with the same signing key.
+			let (state, signature) = signed_amount(1, 40);
+			assert_noop!(
+				TemplateModule::close_one_way_channel(Origin::signed(RECIPIENT), channel_id + 1, state, signature),
+				Error::WrongChannel
+			);
+		});
+	}
+
+	#[test]
+	fn only_participants_can_collateralize() {
+		with_externalities(&mut new_test_ext(), || {
+			let channel_id = open_one_way(100);
+
+			assert_noop!(
+				TemplateModule::collateralize_channel(Origin::signed(3), channel_id, 10),
+				Error::NotParticipant
+			);
+			assert_noop!(
+				TemplateModule::collateralize_channel(Origin::signed(RECIPIENT), channel_id, 10),
+				Error::RecipientCannotCollateralize
+			);
+
+			assert_ok!(TemplateModule::collateralize_channel(Origin::signed(SENDER), channel_id, 50));
+			assert_eq!(TemplateModule::channels(channel_id).collateral, 150);
+			assert_eq!(Balances::free_balance(&TemplateModule::escrow_account(channel_id)), 150);
+		});
+	}
+
+	#[test]
+	fn top_up_keeps_earlier_states_valid() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			let channel_id = open_one_way(100);
+			let (state, signature) = signed_amount(1, 70);
+
+			// The recipient's latest state still closes, the top up goes back to the sender.
+			assert_ok!(TemplateModule::collateralize_channel(Origin::signed(SENDER), channel_id, 1));
+			assert_ok!(TemplateModule::close_one_way_channel(Origin::signed(RECIPIENT), channel_id, state, signature));
+
+			System::set_block_number(11);
+			assert_ok!(TemplateModule::finalize_channel(Origin::signed(RECIPIENT), channel_id));
+			assert_eq!(Balances::free_balance(&SENDER), 930);
+			assert_eq!(Balances::free_balance(&RECIPIENT), 1070);
+			assert_eq!(Balances::free_balance(&TemplateModule::escrow_account(channel_id)), 0);
+
+			// A state cannot claim collateral that was never posted.
+			let channel_id = open_two_way(100, 50);
+			let mut state = channel_state(channel_id, 1, 100, 100);
+			state.collateral.recipient = 100;
+			let (state, signatures) = sign_both(state);
+			assert_noop!(
+				TemplateModule::close_channel(Origin::signed(SENDER), channel_id, state, signatures),
+				Error::ImpossibleState
+			);
+		});
+	}
+
+	#[test]
+	fn undercollateralized_position_can_be_liquidated() {
+		with_externalities(&mut new_test_ext(), || {
+			let channel_id = open_two_way(100, 100);
+
+			// The sender is long 10 units entered at 20.00, a notional of 200.
+			let mut state = channel_state(channel_id, 1, 100, 100);
+			state.position = Some(Position {
+				feed: 0,
+				sender_long: true,
+				quantity: 10,
+				entry_price: Price::new(2000, 2).unwrap(),
+			});
+			let (state, signatures) = sign_both(state);
+
+			PRICE.with(|p| *p.borrow_mut() = Some(Price::new(21, 0).unwrap()));
+			assert_noop!(
+				TemplateModule::liquidate_channel(Origin::signed(3), channel_id, state.clone(), signatures.clone()),
+				Error::SufficientlyCollateralized
+			);
+
+			// At 29.05 the recipient is down 90.5, rounded down to 90, and holds 10 of equity against a requirement
+			// of 29.
+			PRICE.with(|p| *p.borrow_mut() = Some(Price::new(2905, 2).unwrap()));
+
+			// Nothing can be liquidated while the feed is halted.
+			HALTED.with(|h| *h.borrow_mut() = true);
+			assert_noop!(
+				TemplateModule::liquidate_channel(Origin::signed(3), channel_id, state.clone(), signatures.clone()),
+				Error::FeedHalted
+			);
+			HALTED.with(|h| *h.borrow_mut() = false);
+
+			assert_ok!(TemplateModule::liquidate_channel(Origin::signed(3), channel_id, state, signatures));
+			assert_noop!(TemplateModule::finalize_channel(Origin::signed(3), channel_id), Error::ChallengePeriodActive);
+
+			System::set_block_number(10);
+			assert_ok!(TemplateModule::finalize_channel(Origin::signed(3), channel_id));
+			assert_eq!(Balances::free_balance(&SENDER), 1090);
+			assert_eq!(Balances::free_balance(&RECIPIENT), 909);
+			assert_eq!(Balances::free_balance(&3), 1);
+			assert!(!<Channels<Test>>::exists(channel_id));
+		});
+	}
+
+	#[test]
+	fn newer_state_voids_liquidation() {
+		with_externalities(&mut new_test_ext(), || {
+			let channel_id = open_two_way(100, 100);
+
+			let mut stale = channel_state(channel_id, 1, 100, 100);
+			stale.position = Some(Position {
+				feed: 0,
+				sender_long: true,
+				quantity: 10,
+				entry_price: Price::new(2000, 2).unwrap(),
+			});
+			let (stale, stale_signatures) = sign_both(stale);
+			// The position was closed later at a profit for the sender.
+			let (latest, latest_signatures) = sign_both(channel_state(channel_id, 2, 150, 50));
+
+			PRICE.with(|p| *p.borrow_mut() = Some(Price::new(2905, 2).unwrap()));
+			assert_ok!(TemplateModule::liquidate_channel(Origin::signed(3), channel_id, stale, stale_signatures));
+			assert_ok!(TemplateModule::dispute_channel(Origin::signed(RECIPIENT), channel_id, latest, latest_signatures));
+
+			System::set_block_number(10);
+			assert_ok!(TemplateModule::finalize_channel(Origin::signed(3), channel_id));
+			assert_eq!(Balances::free_balance(&SENDER), 1050);
+			assert_eq!(Balances::free_balance(&RECIPIENT), 950);
+			assert_eq!(Balances::free_balance(&3), 0);
+		});
+	}
+
+	#[test]
+	fn settle_pays_out_without_challenge_window() {
+		with_externalities(&mut new_test_ext(), || {
+			let channel_id = open_two_way(100, 100);
+
+			let (state, signatures) = signed_balances(4, 60, 140);
+			assert_noop!(
+				TemplateModule::settle_channel(Origin::signed(SENDER), channel_id, state, signatures),
+				Error::StateNotFinal
+			);
+
+			let mut state = channel_state(channel_id, 5, 60, 140);
+			state.is_final = true;
+			let (state, signatures) = sign_both(state);
+			assert_ok!(TemplateModule::settle_channel(Origin::signed(SENDER), channel_id, state, signatures));
+
+			assert_eq!(Balances::free_balance(&SENDER), 960);
+			assert_eq!(Balances::free_balance(&RECIPIENT), 1040);
+			assert_eq!(Balances::free_balance(&TemplateModule::escrow_account(channel_id)), 0);
+			assert!(!<Channels<Test>>::exists(channel_id));
+		});
+	}
+
+	#[test]
+	fn sender_reclaims_expired_channel() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			let channel_id = open_expiring(100, Some(20));
+
+			assert_noop!(TemplateModule::reclaim_channel(Origin::signed(SENDER), channel_id), Error::NotExpired);
+
+			System::set_block_number(20);
+			assert_ok!(TemplateModule::reclaim_channel(Origin::signed(SENDER), channel_id));
+
+			// The recipient answers within the grace period with the latest state they hold.
+			let (latest, latest_signature) = signed_amount(3, 30);
+			assert_ok!(TemplateModule::dispute_channel(Origin::signed(RECIPIENT), channel_id, latest, vec![latest_signature]));
+
+			System::set_block_number(25);
+			assert_ok!(TemplateModule::finalize_channel(Origin::signed(SENDER), channel_id));
+			assert_eq!(Balances::free_balance(&SENDER), 970);
+			assert_eq!(Balances::free_balance(&RECIPIENT), 1030);
+		});
+	}
+
+	#[test]
+	fn channels_do_not_share_collateral() {
+		with_externalities(&mut new_test_ext(), || {
+			let first = open_one_way(100);
+			let second = open_one_way(200);
+
+			assert_ne!(TemplateModule::escrow_account(first), TemplateModule::escrow_account(second));
+			assert_eq!(Balances::free_balance(&TemplateModule::escrow_account(first)), 100);
+			assert_eq!(Balances::free_balance(&TemplateModule::escrow_account(second)), 200);
+			assert_eq!(Balances::free_balance(&SENDER), 700);
+		});
+	}
+
+	#[test]
+	fn dust_payout_is_burned() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			EXISTENTIAL_DEPOSIT.with(|v| *v.borrow_mut() = 50);
+
+			// The recipient has no account and is owed less than the existential deposit.
+			let public = signing_pair().public().as_ref().to_vec();
+			assert_ok!(TemplateModule::one_way_channel(Origin::signed(SENDER), public, 5, 100, None));
+			let (state, signature) = signed_amount(1, 40);
+			assert_ok!(TemplateModule::close_one_way_channel(Origin::signed(5), 0, state, signature));
+
+			System::set_block_number(11);
+			let issuance = Balances::total_issuance();
+			assert_ok!(TemplateModule::finalize_channel(Origin::signed(5), 0));
+			// The sender keeps only what the state assigns them.
+			assert_eq!(Balances::free_balance(&SENDER), 960);
+			assert_eq!(Balances::free_balance(&5), 0);
+			assert_eq!(Balances::total_issuance(), issuance - 40);
+			assert_eq!(Balances::free_balance(&TemplateModule::escrow_account(0)), 0);
+		});
+	}
+
+	#[test]
+	fn failed_payout_leaves_channel_untouched() {
+		with_externalities(&mut new_test_ext(), || {
+			System::set_block_number(1);
+			let channel_id = open_one_way(100);
+			let (state, signature) = signed_amount(1, 40);
+			assert_ok!(TemplateModule::close_one_way_channel(Origin::signed(RECIPIENT), channel_id, state, signature));
+
+			// Something drained the escrow, the recipient must not be paid out of nothing.
+			Balances::make_free_balance_be(&TemplateModule::escrow_account(channel_id), 30);
+
+			System::set_block_number(11);
+			assert_noop!(
+				TemplateModule::finalize_channel(Origin::signed(RECIPIENT), channel_id),
+				Error::InsufficientEscrow
+			);
+			assert!(<Channels<Test>>::exists(channel_id));
 		});
 	}
 }
@@ -67,6 +67,9 @@ pub type Hash = primitives::H256;
 /// Digest item type.
 pub type DigestItem = generic::DigestItem<Hash>;
 
+/// A timestamp: milliseconds since the unix epoch.
+pub type Moment = u64;
+
 
 /// Opaque types. These are used by the CLI to instantiate machinery that don't need to know
 /// the specifics of the runtime. They can then be made to be agnostic over specific formats
@@ -220,7 +223,7 @@ parameter_types! {
 
 impl timestamp::Trait for Runtime {
 	/// A timestamp: milliseconds since the unix epoch.
-	type Moment = u64;
+	type Moment = Moment;
 	type OnTimestampSet = Babe;
 	type MinimumPeriod = MinimumPeriod;
 }
@@ -324,18 +327,70 @@ impl sudo::Trait for Runtime {
 	type Proposal = Call;
 }
 
+parameter_types! {
+	pub const ChallengePeriod: BlockNumber = 1 * HOURS;
+	pub const ExpiryGracePeriod: BlockNumber = 1 * HOURS;
+	pub const MaintenanceMargin: Perbill = Perbill::from_percent(5);
+	pub const LiquidationFee: Perbill = Perbill::from_percent(1);
+	pub const PriceWindow: u64 = 30 * 60 * 1000;
+}
+
 impl channel::Trait for Runtime {
 	type Event = Event;
 	type Currency = Balances;
+	type ChallengePeriod = ChallengePeriod;
+	type ExpiryGracePeriod = ExpiryGracePeriod;
+	type Oracle = Oracle;
+	type MaintenanceMargin = MaintenanceMargin;
+	type LiquidationFee = LiquidationFee;
+	type PriceWindow = PriceWindow;
 }
 
 type SubmitTransaction = TransactionSubmitter<OracleId, Runtime, UncheckedExtrinsic>;
 
+parameter_types! {
+	pub const OracleBond: Balance = 1_000_000_000_000;
+	pub const OracleQuorum: Perbill = Perbill::from_percent(67);
+	pub const OracleOutlierDeviation: Perbill = Perbill::from_percent(5);
+	pub const OracleMaxOutliers: Perbill = Perbill::from_percent(25);
+	pub const OracleMinParticipation: Perbill = Perbill::from_percent(50);
+	// Six eras, long enough for every feed to finalise several rounds.
+	pub const OracleParticipationPeriod: sr_staking_primitives::SessionIndex = 36;
+	pub const OracleRoundReward: Balance = 1_000_000_000;
+	pub const OracleMaxSessionReward: Balance = 100_000_000_000;
+	pub const OracleMinUpdateInterval: BlockNumber = 1 * MINUTES;
+	// Well within a session, at whose end rounds still taking reveals start over.
+	pub const OracleRevealPeriod: BlockNumber = EPOCH_DURATION_IN_BLOCKS / 2;
+	pub const OracleHistoryPeriod: u64 = 60 * 60 * 1000;
+	pub const OracleMinRoundSpacing: u64 = 60 * 1000;
+}
+
 impl oracle::Trait for Runtime {
 	type AuthorityId = OracleId;
 	type Call = Call;
 	type SubmitTransaction = SubmitTransaction;
 	type Event = Event;
+	type Currency = Balances;
+	type OracleBond = OracleBond;
+	type Quorum = OracleQuorum;
+	type OutlierDeviation = OracleOutlierDeviation;
+	type MaxOutliers = OracleMaxOutliers;
+	type MinParticipation = OracleMinParticipation;
+	type ParticipationPeriod = OracleParticipationPeriod;
+	type RoundReward = OracleRoundReward;
+	type MaxSessionReward = OracleMaxSessionReward;
+	type MinUpdateInterval = OracleMinUpdateInterval;
+	type RevealPeriod = OracleRevealPeriod;
+	type HistoryPeriod = OracleHistoryPeriod;
+	type MinRoundSpacing = OracleMinRoundSpacing;
+	type RewardAccount = ConvertInto;
+	type ReportMisreporting = Offences;
+}
+
+impl offences::Trait for Runtime {
+	type Event = Event;
+	type IdentificationTuple = session::historical::IdentificationTuple<Self>;
+	type OnOffenceHandler = Staking;
 }
 
 construct_runtime!(
@@ -354,7 +409,8 @@ construct_runtime!(
 		Session: session::{Module, Call, Storage, Event, Config<T>},
 		Sudo: sudo,
 		Channel: channel::{Module, Call, Storage, Event<T>},
-		Oracle: oracle::{Module, Call, Storage, Event},
+		Oracle: oracle::{Module, Call, Storage, Event<T>, ValidateUnsigned},
+		Offences: offences::{Module, Call, Storage, Event},
 		RandomnessCollectiveFlip: randomness_collective_flip::{Module, Storage},
 	}
 );
@@ -464,6 +520,18 @@ impl_runtime_apis! {
 		}
 	}
 
+	impl oracle::OracleApi<Block, AccountId, Balance, BlockNumber, Moment> for Runtime {
+		fn feeds(twap_window: u64) -> Vec<oracle::FeedStatus<AccountId, Balance, BlockNumber, Moment>> {
+			(0..Oracle::next_free_id())
+				.filter_map(|feed_id| Oracle::feed_status(feed_id, twap_window))
+				.collect()
+		}
+
+		fn feed(feed_id: oracle::FeedId, twap_window: u64) -> Option<oracle::FeedStatus<AccountId, Balance, BlockNumber, Moment>> {
+			Oracle::feed_status(feed_id, twap_window)
+		}
+	}
+
 	impl substrate_session::SessionKeys<Block> for Runtime {
 		fn generate_session_keys(seed: Option<Vec<u8>>) -> Vec<u8> {
 			let seed = seed.as_ref().map(|s| rstd::str::from_utf8(&s).expect("Seed is an utf8 string"));
@@ -3,21 +3,124 @@
 //! The Oracle module allows for off-chain price feeds to be queried by the validator set and to be recorded on-chain.
 //! It will store the current raw value of the feed as well as a medianized value. These two values are used in
 //! different situations of the channels protocol, depending on the type of close that was chosen.
+//!
+//! Submissions are collected in rounds. Each authority reports at most one value per round and feed, and a round
+//! finalises once the configured quorum of authorities has reported. The median of the round's submissions is
+//! then recorded as the feed's latest value together with the round number. Every aggregate is kept with the time
+//! it was recorded for `HistoryPeriod`, so a time-weighted average price over a window of up to that long can be
+//! read through `OracleProvider`. A feed's next round only opens `MinRoundSpacing` after its last one finalised,
+//! which bounds how many aggregates are kept however often authorities push deviating prices.
+//!
+//! ## Misreporting
+//!
+//! Every finalised round is expected from every validator of the session, and counts towards the statistics of
+//! those that reported on it. A submission deviating from the round's aggregate by more than `OutlierDeviation`
+//! counts as an outlier. A validator whose node does not serve a feed opts out of it explicitly through `skip_feed`,
+//! after which the feed's rounds are no longer expected from it. A round finalises as soon as the quorum reported,
+//! so reports arriving after quorum are still accepted while the next round is open. They count towards the
+//! authority's participation and outliers, but not towards the aggregate or the reward.
+//!
+//! A feed finalises a round every few sessions at most, so the statistics are kept per validator over
+//! `ParticipationPeriod` sessions. When the period's last session ends, the validators that reported on fewer than
+//! `MinParticipation` of the rounds expected from them, or whose submissions were outliers more often than
+//! `MaxOutliers`, are reported as a `MisreportingOffence` to be slashed and chilled by staking.
+//!
+//! ## Rewards
+//!
+//! Every finalised round mints `RoundReward`, split evenly between the validators whose submissions were not
+//! outliers. Anyone can register a feed, so the rounds of a session mint at most `MaxSessionReward` between them,
+//! and a feed cannot be registered with an `update_interval` below `MinUpdateInterval`.
+//!
+//! ## Endpoints
+//!
+//! A feed registered on-chain only describes the data it reports. Where that data is fetched from is up to each
+//! node operator: the offchain worker reads an `Endpoint` for every feed from the node's persistent offchain local
+//! storage under `endpoint_key(feed_id)`, and skips the feeds the node has no endpoint for. The node sets these
+//! with its `--oracle-endpoint` and `--oracle-header` flags.
+//!
+//! Endpoints respond with JSON. Each feed names the path of its value in the response, e.g. `data.price`, and the
+//! decimal found there is reported as a fixed-point `Price` with the feed's decimals.
+//!
+//! ## Provider signatures
+//!
+//! A feed's owner may configure the feed with the public key of its data provider through `set_provider`. Every
+//! submission to such a feed must then carry the provider's signature over the reported price, which `update_feed`
+//! verifies, so the price is proven to come from the provider and not just from the relaying authority. The time the
+//! provider signed the price for may be at most the feed's `max_age` behind the chain's time, and half a minute ahead.
+//! The signed message names the feed's id as well as its description, so a price signed for one feed cannot be
+//! submitted to another. Its format follows the provider's key, see `attestation_message`: an Ethereum provider signs
+//! the ABI encoded price as an Ethereum signed message, the way `eth_sign` and its libraries do.
+//!
+//! ## Circuit breaker
+//!
+//! A feed is halted when its latest aggregate is older than the feed's `max_age`, or when a round's aggregate moves
+//! further from the previous one than the feed's `max_jump`. Consumers check `OracleProvider::is_halted` and do not
+//! act on a halted feed's prices. The next round that finalises within `max_jump` of the previous aggregate resumes
+//! the feed, as does `resume_feed` from root for a jump. The age is checked whenever the feed is read rather than by
+//! a sweep over all feeds each block, so a feed counts as halted once stale even before anyone records it. Anyone may
+//! then call `report_stale` to record the halt and emit `FeedHalted` for it.
+//!
+//! ## Throttling
+//!
+//! A node submits to a feed at most once per round, and only opens a new round once the feed's `update_interval`
+//! has passed since its last submission, or when the fetched price deviates from the latest aggregate by more than
+//! the feed's `deviation`. It always joins rounds other authorities have already opened, so they reach quorum. The
+//! last submission and a per-feed lock, which keeps overlapping worker runs from fetching and submitting twice,
+//! are kept in the node's offchain local storage.
+//!
+//! ## Commit-reveal
+//!
+//! An authority could wait for the others' submissions to a round and copy them instead of querying the feed. A
+//! feed's owner can prevent this by switching the feed to commit-reveal through `set_commit_reveal`. Authorities
+//! then first submit only the `commitment` to their price and a random salt through `commit_feed`. Once the
+//! quorum has committed, the round stops taking commitments and the authorities reveal their prices and salts
+//! through `update_feed`. Only revealed prices matching the authority's commitment count towards the round, which
+//! finalises with the quorum of reveals as before. Authorities that commit while the round takes reveals may reveal
+//! too, so the round finalises once any quorum of the committed authorities revealed. Nodes keep the committed price
+//! and salt in their offchain local storage until they reveal. A round that does not finalise within `RevealPeriod`
+//! blocks of taking reveals, or by the end of the session, starts over under the next id, and the authorities that
+//! committed to it without revealing count as having missed it. Authorities that commit and never reveal therefore
+//! neither stall the feed nor escape the misreporting checks.
 //! 
 //! ## Interface
 //! 
 //! ### Public Functions
 //! 
-//! - `register_new_oracle` - Registers a new endpoint for which the system will begin to query. Requires a bond.
+//! - `register_new_oracle` - Registers a new feed for which the system will begin to query. Requires a bond.
+//! - `set_provider` - Requires the feed's prices to be signed by the given data provider. Owner only.
+//! - `set_commit_reveal` - Switches the feed's rounds to or from commit-reveal. Owner only.
+//! - `resume_feed` - Resumes a halted feed, root only.
+//! - `skip_feed` - Stops or resumes expecting the caller's validator to report on a feed.
+//! - `report_stale` - Records the halt of a feed whose latest aggregate is older than its maximum age.
+//! - `commit_feed` - Unsigned transaction through which the validators' offchain workers commit to a feed's value.
+//! - `update_feed` - Unsigned transaction through which the validators' offchain workers report a feed's value.
+//!
+//! ### Runtime API
+//!
+//! `OracleApi` serves each feed's definition, latest and median price, time-weighted average price and halt status
+//! to the node, so clients do not need to decode the module's storage.
 
-use codec::{Encode, Decode};
+use client::decl_runtime_apis;
+use codec::{Codec, Encode, Decode};
+use primitives::{ed25519, H160, H256};
+use primitives::offchain::{Duration, HttpError, HttpRequestStatus, StorageKind};
 use rstd::prelude::*;
 use sr_primitives::app_crypto::RuntimeAppPublic;
-use sr_primitives::traits::Member;
+use sr_primitives::Perbill;
+use sr_primitives::traits::{Convert, Member, SaturatedConversion, Saturating, Zero};
 use sr_primitives::transaction_validity::{TransactionValidity, TransactionLongevity, ValidTransaction, InvalidTransaction};
-use support::{decl_module, decl_event, decl_storage, Parameter, StorageMap, StorageValue};
-use system::ensure_none;
+use rstd::convert::TryFrom;
+use rstd::result;
+use session::historical::IdentificationTuple;
+use sr_staking_primitives::SessionIndex;
+use sr_staking_primitives::offence::{Kind, Offence, ReportOffence};
+use support::{decl_module, decl_event, decl_storage, decl_error, ensure, Parameter, StorageMap, StorageValue};
+use support::traits::{Currency, ReservableCurrency, Get};
+use system::{ensure_none, ensure_root, ensure_signed};
 use system::offchain::SubmitUnsignedTransaction;
+use tiedye_primitives::Price;
+
+mod json;
 
 pub mod sr25519 {
     mod app_sr25519 {
@@ -40,21 +143,361 @@ pub mod sr25519 {
     pub type AuthorityId = app_sr25519::Public;
 }
 
+/// The identifier of a registered feed.
+pub type FeedId = u32;
+
+/// The longest description or path a feed can be registered with.
+const MAX_FIELD_LENGTH: usize = 256;
+
+/// The prefix of the offchain local storage keys feed endpoints are configured under.
+const ENDPOINT_KEY_PREFIX: &[u8] = b"tiedye/oracle/endpoint/";
+
+/// The prefix of the offchain local storage keys a node's last submission to each feed is kept under.
+const SUBMISSION_KEY_PREFIX: &[u8] = b"tiedye/oracle/submission/";
+
+/// The prefix of the offchain local storage keys a node's pending commitment to each feed is kept under.
+const COMMIT_KEY_PREFIX: &[u8] = b"tiedye/oracle/commit/";
+
+/// The prefix of the offchain local storage keys of the per-feed worker locks.
+const LOCK_KEY_PREFIX: &[u8] = b"tiedye/oracle/lock/";
+
+/// The number of blocks after which a worker lock is considered abandoned.
+const LOCK_PERIOD: u32 = 10;
+
+/// The largest endpoint response accepted, in bytes.
+const MAX_RESPONSE_LENGTH: usize = 16 * 1024;
+
+/// How long a single request to an endpoint may take, in milliseconds.
+const HTTP_DEADLINE_MS: u64 = 2_000;
+
+/// How often a request failing for a possibly transient reason is tried again before the feed is skipped.
+const HTTP_RETRIES: usize = 2;
+
+/// How far ahead of the chain's time a provider may sign a price for, in seconds.
+const MAX_CLOCK_SKEW_SECS: u64 = 30;
+
+/// A feed registered for the validators to query.
+#[derive(Default, Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Feed<AccountId, Balance, BlockNumber, Moment> {
+    /// What the feed reports, e.g. `BTC/USD`.
+    pub description: Vec<u8>,
+    /// Where the value is found in the endpoint's JSON response, e.g. `data.price` or `result.0.last`.
+    pub path: Vec<u8>,
+    /// The number of decimals of the feed's prices, at most `Price::MAX_DECIMALS`.
+    pub decimals: u8,
+    /// The number of blocks a node waits after submitting before it opens a new round.
+    pub update_interval: BlockNumber,
+    /// How far the price may move from the latest aggregate before a node opens a new round early.
+    pub deviation: Perbill,
+    /// How old the latest aggregate may get before the feed is halted as stale.
+    pub max_age: Moment,
+    /// How far a round's aggregate may move from the previous one before the feed is halted.
+    pub max_jump: Perbill,
+    /// The data provider that must sign the feed's prices, if any.
+    pub provider: Option<Provider>,
+    /// Whether authorities commit to their prices before revealing them.
+    pub commit_reveal: bool,
+    /// The account that registered the feed.
+    pub owner: AccountId,
+    /// The amount reserved from the owner for as long as the feed is registered.
+    pub bond: Balance,
+}
+
+/// The public key a data provider signs its prices with, which also selects the format of the signed message.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum ProviderKey {
+    /// An ed25519 public key, signing the SCALE encoded message.
+    Ed25519(ed25519::Public),
+    /// The Ethereum address of a secp256k1 key, signing the ABI encoded message as an Ethereum signed message.
+    Secp256k1(H160),
+}
+
+/// A data provider publishing signed prices.
+///
+/// The provider signs the feed's id, the feed's description, the price at the feed's decimals and the seconds since
+/// the unix epoch the price is for, encoded as `attestation_message` describes for the provider's key.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Provider {
+    pub key: ProviderKey,
+    /// Where the hex encoded signature is found in the endpoint's JSON response.
+    pub signature_path: Vec<u8>,
+    /// Where the timestamp is found in the endpoint's JSON response.
+    pub timestamp_path: Vec<u8>,
+}
+
+/// A data provider's signature over a reported price.
+#[derive(Default, Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Attestation {
+    /// The seconds since the unix epoch the provider signed the price for.
+    timestamp: u64,
+    signature: Vec<u8>,
+}
+
+type FeedOf<T> = Feed<
+    <T as system::Trait>::AccountId,
+    BalanceOf<T>,
+    <T as system::Trait>::BlockNumber,
+    <T as timestamp::Trait>::Moment,
+>;
+
+/// Why a feed was halted.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum HaltReason {
+    /// No round finalised within the feed's maximum age.
+    Stale,
+    /// A round's aggregate moved further from the previous one than the feed allows.
+    PriceJump,
+}
+
+/// A feed's definition and current prices, as served by `OracleApi`.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct FeedStatus<AccountId, Balance, BlockNumber, Moment> {
+    pub id: FeedId,
+    pub feed: Feed<AccountId, Balance, BlockNumber, Moment>,
+    /// The aggregate of the latest finalised round, `None` before the first.
+    pub latest: Option<Price>,
+    /// The median of the recent aggregates, `None` before the first.
+    pub median: Option<Price>,
+    /// The time-weighted average price over the requested window, `None` if the kept aggregates do not reach back
+    /// over the whole window, as before the first one or for a window longer than `HistoryPeriod`.
+    pub twap: Option<Price>,
+    /// When the latest aggregate was recorded, `None` before the first.
+    pub last_update: Option<Moment>,
+    /// Whether the latest aggregate is older than the feed's maximum age, which halts the feed.
+    pub stale: bool,
+    /// Why the feed is halted, `None` if it is running.
+    pub halted: Option<HaltReason>,
+}
+
+type FeedStatusOf<T> = FeedStatus<
+    <T as system::Trait>::AccountId,
+    BalanceOf<T>,
+    <T as system::Trait>::BlockNumber,
+    <T as timestamp::Trait>::Moment,
+>;
+
+decl_runtime_apis! {
+    /// Reads the oracle's feeds.
+    pub trait OracleApi<AccountId, Balance, BlockNumber, Moment> where
+        AccountId: Codec,
+        Balance: Codec,
+        BlockNumber: Codec,
+        Moment: Codec,
+    {
+        /// Every registered feed, with time-weighted average prices over the last `twap_window` milliseconds.
+        fn feeds(twap_window: u64) -> Vec<FeedStatus<AccountId, Balance, BlockNumber, Moment>>;
+
+        /// The feed registered under `feed_id`, with its time-weighted average price over the last `twap_window`
+        /// milliseconds.
+        fn feed(feed_id: FeedId, twap_window: u64) -> Option<FeedStatus<AccountId, Balance, BlockNumber, Moment>>;
+    }
+}
+
+/// Where a node fetches a feed from, configured by the node operator in offchain local storage.
+#[derive(Default, Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Endpoint {
+    /// The URL to query.
+    pub url: Vec<u8>,
+    /// Headers sent along with the request, e.g. an API key.
+    pub headers: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+/// The offchain local storage key the endpoint of `feed_id` is configured under. An empty value configures no
+/// endpoint, as local storage values cannot be removed.
+pub fn endpoint_key(feed_id: FeedId) -> Vec<u8> {
+    local_key(ENDPOINT_KEY_PREFIX, feed_id)
+}
+
+/// The offchain local storage key the node keeps the feeds it configured endpoints for under, so it can clear
+/// those no longer configured.
+pub const CONFIGURED_FEEDS_KEY: &[u8] = b"tiedye/oracle/configured-feeds";
+
+fn local_key(prefix: &[u8], feed_id: FeedId) -> Vec<u8> {
+    let mut key = prefix.to_vec();
+    key.extend(feed_id.encode());
+    key
+}
+
+/// A node's last submission to a feed, kept in its offchain local storage.
+#[derive(Encode, Decode)]
+struct LastSubmission<BlockNumber> {
+    block: BlockNumber,
+    round: RoundId,
+}
+
+/// A commitment a node submitted and has yet to reveal, kept in its offchain local storage.
+#[derive(Encode, Decode)]
+struct PendingCommit {
+    round: RoundId,
+    value: Price,
+    salt: H256,
+    attestation: Option<Attestation>,
+}
+
+/// The number of a feed's aggregation round.
+pub type RoundId = u32;
+
+/// The submissions collected for a feed's current round.
+#[derive(Default, Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Round {
+    id: RoundId,
+    /// The session the round collects for. Submissions and commitments are indexed into its keys.
+    session: SessionIndex,
+    /// The price reported by each authority that took part so far.
+    submissions: Vec<(AuthIndex, Price)>,
+    /// The commitment of each authority that committed so far, on commit-reveal feeds.
+    commits: Vec<(AuthIndex, H256)>,
+    /// Whether the quorum committed and the round takes reveals, on commit-reveal feeds.
+    revealing: bool,
+    /// The commitments that arrived once the round took reveals, on commit-reveal feeds. Their reveals count like
+    /// the others.
+    late_commits: Vec<(AuthIndex, H256)>,
+    /// The previous round, which still takes reports that arrived after its quorum. `None` once the set of
+    /// authorities changed since.
+    previous: Option<PreviousRound>,
+}
+
+/// A finalised round, kept while the next one is open.
+#[derive(Default, Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct PreviousRound {
+    /// The authorities that reported on the round, before or after its quorum.
+    reporters: Vec<AuthIndex>,
+    /// The commitments made to the round, on commit-reveal feeds.
+    commits: Vec<(AuthIndex, H256)>,
+}
+
+/// How a report to a feed counts.
+enum ReportKind {
+    /// Towards the current round's aggregate.
+    Aggregate,
+    /// Towards the reporter's participation in the previous round only, as it arrived after quorum.
+    PreviousRound,
+}
+
+/// The commitment an authority submits to `value` for a round of a commit-reveal feed, hiding the value behind a
+/// random `salt` until it is revealed. It covers the authority's index so another authority's commitment cannot
+/// be copied either.
+pub fn commitment(feed_id: FeedId, round: RoundId, authority_index: AuthIndex, value: Price, salt: &H256) -> H256 {
+    H256(runtime_io::blake2_256(&(feed_id, round, authority_index, value, salt).encode()))
+}
+
+/// The message a provider with `key` signs to attest `price` for `feed_id` at `timestamp`.
+///
+/// An ed25519 provider signs the SCALE encoding of `(feed_id, description, price, timestamp)`. A secp256k1 provider
+/// signs `"\x19Ethereum Signed Message:\n32"` followed by the keccak-256 hash of the ABI encoding of
+/// `(uint256 feedId, string description, uint256 price, uint256 timestamp)`, with `price` the fixed-point value at
+/// the feed's decimals, which is what `eth_sign` and Ethereum libraries' `signMessage` produce for that hash.
+pub fn attestation_message(
+    key: &ProviderKey,
+    feed_id: FeedId,
+    description: &[u8],
+    price: Price,
+    timestamp: u64,
+) -> Vec<u8> {
+    match key {
+        ProviderKey::Ed25519(_) => (feed_id, description, price, timestamp).encode(),
+        ProviderKey::Secp256k1(_) => {
+            let hash = runtime_io::keccak_256(&abi_encode(feed_id, description, price, timestamp));
+            let mut message = b"\x19Ethereum Signed Message:\n32".to_vec();
+            message.extend_from_slice(&hash);
+            message
+        }
+    }
+}
+
+/// The ABI encoding of `(uint256 feedId, string description, uint256 price, uint256 timestamp)`.
+fn abi_encode(feed_id: FeedId, description: &[u8], price: Price, timestamp: u64) -> Vec<u8> {
+    fn word(value: u128) -> [u8; 32] {
+        let mut word = [0u8; 32];
+        word[16..].copy_from_slice(&value.to_be_bytes());
+        word
+    }
+
+    let mut encoded = Vec::with_capacity(6 * 32 + description.len());
+    encoded.extend_from_slice(&word(feed_id.into()));
+    // The string is a dynamic type, its place holds the offset of its contents after the four head words.
+    encoded.extend_from_slice(&word(4 * 32));
+    encoded.extend_from_slice(&word(price.value()));
+    encoded.extend_from_slice(&word(timestamp.into()));
+    encoded.extend_from_slice(&word(description.len() as u128));
+    encoded.extend_from_slice(description);
+    // The contents are padded to whole words.
+    encoded.resize(encoded.len() + (32 - description.len() % 32) % 32, 0);
+    encoded
+}
+
+/// A finalised round's aggregate and when it was recorded.
+#[derive(Default, Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Observation<Moment> {
+    price: Price,
+    timestamp: Moment,
+}
+
+/// A feed's latest aggregate and median, kept apart from its history so reading them stays cheap.
 #[derive(Default, Encode, Decode)]
 #[cfg_attr(feature = "std", derive(Debug))]
 pub struct OracleResult<Moment> {
-    values: Vec<u32>,
+    /// The aggregate of the most recently finalised round.
+    latest: Price,
+    /// The round `latest` was finalised in.
+    round: RoundId,
+    /// The median of the feed's `History`, kept up to date so readers never load or sort the history.
+    median: Price,
     // Last update.
     last_update: Moment,
 }
 
+/// How a validator reported during the current participation period.
+#[derive(Default, Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct ReporterRecord {
+    /// The number of finalised rounds the validator was expected to report on, those of the feeds it did not skip.
+    rounds: u32,
+    /// The number of finalised rounds the authority reported on.
+    submissions: u32,
+    /// The number of those submissions that deviated from the round's aggregate by more than allowed.
+    outliers: u32,
+}
+
 /// Error which may occur while executing the off-chain code.
+#[derive(PartialEq)]
 #[cfg_attr(feature = "std", derive(Debug))]
 enum OffchainErr {
 	DecodeWorkerStatus,
 	FailedSigning,
 	NetworkState,
 	SubmitTransaction,
+	HttpRequest,
+	HttpTimeout,
+	HttpIo,
+	HttpStatus(u16),
+	ResponseTooLarge,
+	DecodeResponse,
+	InvalidEndpoint,
+	DecodeEndpoint,
+	DecodeAttestation,
+}
+
+impl OffchainErr {
+	/// Whether trying the same request again right away may succeed. A rate limited request is not, retrying it
+	/// back to back only hits the limit again, the worker tries again in a later block.
+	fn is_transient(&self) -> bool {
+		match self {
+			OffchainErr::HttpTimeout | OffchainErr::HttpIo => true,
+			OffchainErr::HttpStatus(code) => *code >= 500,
+			_ => false,
+		}
+	}
 }
 
 impl support::Printable for OffchainErr {
@@ -64,6 +507,18 @@ impl support::Printable for OffchainErr {
 			OffchainErr::FailedSigning => support::print("Offchain error: signing failed!"),
 			OffchainErr::NetworkState => support::print("Offchain error: fetching network state failed!"),
 			OffchainErr::SubmitTransaction => support::print("Offchain error: submitting transaction failed!"),
+			OffchainErr::HttpRequest => support::print("Offchain error: starting the http request failed!"),
+			OffchainErr::HttpTimeout => support::print("Offchain error: the http request timed out!"),
+			OffchainErr::HttpIo => support::print("Offchain error: the http request failed!"),
+			OffchainErr::HttpStatus(code) => {
+				support::print("Offchain error: the endpoint responded with http status");
+				support::print(*code as u64);
+			},
+			OffchainErr::ResponseTooLarge => support::print("Offchain error: the http response is too large!"),
+			OffchainErr::DecodeResponse => support::print("Offchain error: no decimal found at the feed's path in the response!"),
+			OffchainErr::InvalidEndpoint => support::print("Offchain error: configured endpoint is not valid utf8!"),
+			OffchainErr::DecodeEndpoint => support::print("Offchain error: decoding the configured endpoint failed!"),
+			OffchainErr::DecodeAttestation => support::print("Offchain error: no provider signature found in the response!"),
 		}
 	}
 }
@@ -73,16 +528,105 @@ pub type AuthIndex = u32;
 #[derive(Default, Encode, Decode, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "std", derive(Debug))]
 pub struct OracleMessage {
-    value: u32,
+    feed_id: FeedId,
+    /// The round the value is reported for, must be the feed's current round.
+    round: RoundId,
+    /// The reported price, at the feed's decimals.
+    value: Price,
+    /// The data provider's signature over the price, required if the feed has a provider.
+    attestation: Option<Attestation>,
+    /// The salt of the authority's commitment to the price, required if the feed is commit-reveal.
+    salt: Option<H256>,
+    authority_index: AuthIndex,
+}
+
+/// An authority's commitment to its price for a round of a commit-reveal feed.
+#[derive(Default, Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct OracleCommit {
+    feed_id: FeedId,
+    /// The round the commitment is for, must be the feed's current round.
+    round: RoundId,
+    /// The `commitment` to the price the authority reveals later.
+    commitment: H256,
     authority_index: AuthIndex,
 }
 
-pub trait Trait: system::Trait + timestamp::Trait + session::Trait {
+/// Read access to the oracle's feeds for other modules.
+pub trait OracleProvider {
+    /// The medianized price of `feed`, `None` if the feed has not reported yet.
+    fn median(feed: FeedId) -> Option<Price>;
+
+    /// The latest raw price of `feed`, `None` if the feed has not reported yet.
+    fn latest(feed: FeedId) -> Option<Price>;
+
+    /// The time-weighted average price of `feed` over the last `window` milliseconds, `None` if the feed's kept
+    /// aggregates do not reach back over the whole window.
+    fn twap(feed: FeedId, window: u64) -> Option<Price>;
+
+    /// Whether `feed` is halted, in which case its prices must not be acted on.
+    fn is_halted(feed: FeedId) -> bool;
+}
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+
+pub trait Trait: system::Trait + timestamp::Trait + session::historical::Trait {
     /// The identifier type for an authority.
     type AuthorityId: Member + Parameter + RuntimeAppPublic + Default + Ord;
 
     /// The overarching event type.
-    type Event: From<Event> + Into<<Self as system::Trait>::Event>;
+    type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+    /// The currency feed bonds are reserved in.
+    type Currency: ReservableCurrency<Self::AccountId>;
+
+    /// The bond reserved from the owner of a new feed.
+    type OracleBond: Get<BalanceOf<Self>>;
+
+    /// The share of the current authorities that must report before a round finalises.
+    type Quorum: Get<Perbill>;
+
+    /// How far, relative to the aggregate, a submission may deviate before it counts as an outlier.
+    type OutlierDeviation: Get<Perbill>;
+
+    /// The share of an authority's submissions in a participation period that may be outliers before it is reported.
+    type MaxOutliers: Get<Perbill>;
+
+    /// The share of a participation period's rounds an authority must submit to before it is reported.
+    type MinParticipation: Get<Perbill>;
+
+    /// The number of sessions over which participation and outliers are counted before misreporting is reported.
+    type ParticipationPeriod: Get<SessionIndex>;
+
+    /// The amount minted for each finalised round and split between the authorities that were not outliers.
+    type RoundReward: Get<BalanceOf<Self>>;
+
+    /// The most minted for the rounds of a session, over all feeds.
+    type MaxSessionReward: Get<BalanceOf<Self>>;
+
+    /// The shortest update interval a feed can be registered with.
+    type MinUpdateInterval: Get<Self::BlockNumber>;
+
+    /// How many blocks a commit-reveal round takes reveals before it starts over. Rounds also start over at the end
+    /// of the session, so this should be well within a session.
+    type RevealPeriod: Get<Self::BlockNumber>;
+
+    /// How long a feed's aggregates are kept for its time-weighted average price, in milliseconds. Must cover the
+    /// longest window consumers read.
+    type HistoryPeriod: Get<Self::Moment>;
+
+    /// How long after a feed's round finalised its next round may open, in milliseconds.
+    type MinRoundSpacing: Get<Self::Moment>;
+
+    /// The account a validator's oracle rewards are paid to.
+    type RewardAccount: Convert<Self::ValidatorId, Self::AccountId>;
+
+    /// Where misreporting authorities are reported at the end of a session.
+    type ReportMisreporting: ReportOffence<
+        Self::AccountId,
+        IdentificationTuple<Self>,
+        MisreportingOffence<IdentificationTuple<Self>>,
+    >;
 
     /// A dispatchable call type.
     type Call: From<Call<Self>>;
@@ -91,176 +635,2043 @@ pub trait Trait: system::Trait + timestamp::Trait + session::Trait {
     type SubmitTransaction: SubmitUnsignedTransaction<Self, <Self as Trait>::Call>;
 }
 
+decl_error! {
+    /// Errors of the oracle module.
+    pub enum Error {
+        /// The message was signed by an authority index outside the current set of keys.
+        UnknownAuthority,
+        /// No feed is registered with the given id.
+        UnknownFeed,
+        /// The description or path is longer than allowed.
+        FieldTooLong,
+        /// The owner cannot afford the feed bond.
+        InsufficientBond,
+        /// The feed would have more decimals than a `Price` supports.
+        TooManyDecimals,
+        /// The feed's update interval is shorter than `MinUpdateInterval`.
+        UpdateIntervalTooShort,
+        /// The reported price does not have the feed's decimals.
+        WrongDecimals,
+        /// The message reports on a round other than the feed's current one.
+        WrongRound,
+        /// The feed's last round finalised less than `MinRoundSpacing` ago, its next round cannot open yet.
+        RoundTooSoon,
+        /// The authority already reported a value for this round.
+        AlreadySubmitted,
+        /// The feed is not halted.
+        NotHalted,
+        /// The feed is already halted.
+        AlreadyHalted,
+        /// The feed's latest aggregate is within its maximum age.
+        NotStale,
+        /// Only the feed's owner may change it.
+        NotOwner,
+        /// The account does not control a validator.
+        NotValidator,
+        /// The feed requires the provider's signature over the price.
+        MissingAttestation,
+        /// The provider's signature is older than the feed's maximum age.
+        StaleAttestation,
+        /// The provider's signature is for a time too far ahead of the chain's.
+        FutureAttestation,
+        /// The provider's signature does not match the price.
+        InvalidAttestation,
+        /// The feed does not take commitments.
+        NotCommitReveal,
+        /// The round of the commit-reveal feed does not take reveals until the quorum committed.
+        CommitPhase,
+        /// The authority did not commit to a price for this round.
+        NoCommitment,
+        /// The revealed price and salt do not match the authority's commitment.
+        CommitmentMismatch,
+    }
+}
+
 decl_event!(
-    pub enum Event {
-        Hi(),
+    pub enum Event<T> where
+        AccountId = <T as system::Trait>::AccountId,
+        Balance = BalanceOf<T>,
+        ValidatorId = <T as session::Trait>::ValidatorId
+    {
+        /// A new feed was registered by the given account.
+        OracleRegistered(FeedId, AccountId),
+        /// A feed's round reached quorum and finalised with the given aggregate price.
+        RoundFinalized(FeedId, RoundId, Price),
+        /// An account was rewarded for its validator's submission to a feed's round.
+        OracleRewarded(FeedId, RoundId, AccountId, Balance),
+        /// A feed was halted, its prices must not be acted on.
+        FeedHalted(FeedId, HaltReason),
+        /// A halted feed resumed.
+        FeedResumed(FeedId),
+        /// The data provider that must sign a feed's prices changed.
+        ProviderChanged(FeedId),
+        /// A feed was switched to or from commit-reveal, its current round started over.
+        CommitRevealChanged(FeedId, bool),
+        /// The quorum committed to a round of a commit-reveal feed, which now takes reveals.
+        RoundRevealing(FeedId, RoundId),
+        /// A round of a commit-reveal feed did not finalise in time and started over.
+        RoundAbandoned(FeedId, RoundId),
+        /// A validator opted out of or back into reporting on a feed.
+        FeedSkipped(ValidatorId, FeedId, bool),
     }
 );
 
 decl_storage! {
     trait Store for Module<T: Trait> as OracleStorage {
-        /// The oracle endpoints to query.
-        // Oracles get(oracles): map u32 => ;
+        /// The feeds to query.
+        Oracles get(oracles): map FeedId => Option<FeedOf<T>>;
 
         /// The current set of keys that can sign oracle fetching.
         Keys get(keys): Vec<T::AuthorityId>;
 
         /// The results from querying.
-        Results get(results): map u32 => OracleResult<T::Moment>;
+        Results get(results): map FeedId => OracleResult<T::Moment>;
+
+        /// The aggregates of each feed's rounds finalised within `HistoryPeriod`, and the last one before, oldest
+        /// first.
+        History get(history): map FeedId => Vec<Observation<T::Moment>>;
+
+        /// The feeds halted by a price jump, or recorded as stale through `report_stale`. A stale feed counts as
+        /// halted without an entry too.
+        Halted get(halted): map FeedId => Option<HaltReason>;
+
+        /// The round currently collecting submissions for each feed.
+        Rounds get(rounds): map FeedId => Round;
+
+        /// The last block each feed's round takes reveals in, once the quorum committed to it.
+        RevealDeadlines get(reveal_deadline): map FeedId => Option<T::BlockNumber>;
+
+        /// The feeds whose round takes reveals, checked for an expired deadline every block.
+        RevealingFeeds get(revealing_feeds): Vec<FeedId>;
 
-        NextFreeId: u32;
+        /// The feeds each validator opted out of reporting on.
+        SkippedFeeds get(skipped_feeds): double_map T::ValidatorId, blake2_256(FeedId) => bool;
+
+        /// The amount minted for the rounds of the current session so far.
+        SessionReward get(session_reward): BalanceOf<T>;
+
+        /// How each validator reported so far during the current participation period.
+        ReporterRecords get(reporter_records): Vec<(T::ValidatorId, ReporterRecord)>;
+
+        /// The id the next registered feed gets.
+        NextFreeId get(next_free_id): FeedId;
     }
 }
 
 decl_module! {
     pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        type Error = Error;
+
         fn deposit_event() = default;
 
-        fn update_feed(origin, message: OracleMessage, signature: <T::AuthorityId as RuntimeAppPublic>::Signature) {
+        /// The bond reserved from the owner of a new feed.
+        const OracleBond: BalanceOf<T> = T::OracleBond::get();
+
+        /// The share of the current authorities that must report before a round finalises.
+        const Quorum: Perbill = T::Quorum::get();
+
+        /// How far, relative to the aggregate, a submission may deviate before it counts as an outlier.
+        const OutlierDeviation: Perbill = T::OutlierDeviation::get();
+
+        /// The share of an authority's submissions in a participation period that may be outliers before it is
+        /// reported.
+        const MaxOutliers: Perbill = T::MaxOutliers::get();
+
+        /// The share of a participation period's rounds an authority must submit to before it is reported.
+        const MinParticipation: Perbill = T::MinParticipation::get();
+
+        /// The number of sessions over which participation and outliers are counted.
+        const ParticipationPeriod: SessionIndex = T::ParticipationPeriod::get();
+
+        /// The amount minted for each finalised round and split between the authorities that were not outliers.
+        const RoundReward: BalanceOf<T> = T::RoundReward::get();
+
+        /// The most minted for the rounds of a session, over all feeds.
+        const MaxSessionReward: BalanceOf<T> = T::MaxSessionReward::get();
+
+        /// The shortest update interval a feed can be registered with.
+        const MinUpdateInterval: T::BlockNumber = T::MinUpdateInterval::get();
+
+        /// How many blocks a commit-reveal round takes reveals before it starts over.
+        const RevealPeriod: T::BlockNumber = T::RevealPeriod::get();
+
+        /// How long a feed's aggregates are kept for its time-weighted average price, in milliseconds.
+        const HistoryPeriod: T::Moment = T::HistoryPeriod::get();
+
+        /// How long after a feed's round finalised its next round may open, in milliseconds.
+        const MinRoundSpacing: T::Moment = T::MinRoundSpacing::get();
+
+        /// Registers a new feed for the validators to query, reserving the oracle bond from the caller.
+        pub fn register_new_oracle(
+            origin,
+            description: Vec<u8>,
+            path: Vec<u8>,
+            decimals: u8,
+            update_interval: T::BlockNumber,
+            deviation: Perbill,
+            max_age: T::Moment,
+            max_jump: Perbill
+        ) -> result::Result<(), Error> {
+            let owner = ensure_signed(origin)?;
+
+            ensure!(description.len() <= MAX_FIELD_LENGTH, Error::FieldTooLong);
+            ensure!(path.len() <= MAX_FIELD_LENGTH, Error::FieldTooLong);
+            ensure!(decimals <= Price::MAX_DECIMALS, Error::TooManyDecimals);
+            ensure!(update_interval >= T::MinUpdateInterval::get(), Error::UpdateIntervalTooShort);
+
+            let bond = T::OracleBond::get();
+            T::Currency::reserve(&owner, bond).map_err(|_| Error::InsufficientBond)?;
+
+            let feed_id = Self::new_id();
+            <Oracles<T>>::insert(feed_id, Feed {
+                description,
+                path,
+                decimals,
+                update_interval,
+                deviation,
+                max_age,
+                max_jump,
+                provider: None,
+                commit_reveal: false,
+                owner: owner.clone(),
+                bond,
+            });
+
+            Self::deposit_event(RawEvent::OracleRegistered(feed_id, owner));
+
+            Ok(())
+        }
+
+        /// Requires the feed's prices to be signed by `provider`, or no longer if `None`. Only the feed's owner may
+        /// call this.
+        pub fn set_provider(origin, feed_id: FeedId, provider: Option<Provider>) -> result::Result<(), Error> {
+            let who = ensure_signed(origin)?;
+            let mut feed = Self::oracles(feed_id).ok_or(Error::UnknownFeed)?;
+            ensure!(feed.owner == who, Error::NotOwner);
+            if let Some(ref provider) = provider {
+                ensure!(provider.signature_path.len() <= MAX_FIELD_LENGTH, Error::FieldTooLong);
+                ensure!(provider.timestamp_path.len() <= MAX_FIELD_LENGTH, Error::FieldTooLong);
+            }
+
+            feed.provider = provider;
+            <Oracles<T>>::insert(feed_id, feed);
+            Self::deposit_event(RawEvent::ProviderChanged(feed_id));
+
+            Ok(())
+        }
+
+        /// Switches the feed's rounds to commit-reveal, or back. The current round starts over, as its submissions
+        /// were collected under the other mode. Only the feed's owner may call this.
+        pub fn set_commit_reveal(origin, feed_id: FeedId, enabled: bool) -> result::Result<(), Error> {
+            let who = ensure_signed(origin)?;
+            let mut feed = Self::oracles(feed_id).ok_or(Error::UnknownFeed)?;
+            ensure!(feed.owner == who, Error::NotOwner);
+
+            feed.commit_reveal = enabled;
+            <Oracles<T>>::insert(feed_id, feed);
+            let session = <session::Module<T>>::current_index();
+            <Rounds>::mutate(feed_id, |round| *round = Round { id: round.id + 1, session, ..Default::default() });
+            Self::stop_revealing(feed_id);
+            Self::deposit_event(RawEvent::CommitRevealChanged(feed_id, enabled));
+
+            Ok(())
+        }
+
+        /// Resumes a halted feed, e.g. once a price jump turned out to be genuine.
+        pub fn resume_feed(origin, feed_id: FeedId) -> result::Result<(), Error> {
+            ensure_root(origin)?;
+            ensure!(<Halted>::exists(feed_id), Error::NotHalted);
+
+            <Halted>::remove(feed_id);
+            Self::deposit_event(RawEvent::FeedResumed(feed_id));
+
+            Ok(())
+        }
+
+        /// Opts the caller's validator out of reporting on a feed its node does not serve, or back in, so the feed's
+        /// rounds are not held against its participation.
+        pub fn skip_feed(origin, feed_id: FeedId, skip: bool) -> result::Result<(), Error> {
+            let who = ensure_signed(origin)?;
+            let validator = T::ValidatorIdOf::convert(who).ok_or(Error::NotValidator)?;
+            ensure!(<Oracles<T>>::exists(feed_id), Error::UnknownFeed);
+
+            if skip {
+                <SkippedFeeds<T>>::insert(&validator, &feed_id, true);
+            } else {
+                <SkippedFeeds<T>>::remove(&validator, &feed_id);
+            }
+            Self::deposit_event(RawEvent::FeedSkipped(validator, feed_id, skip));
+
+            Ok(())
+        }
+
+        /// Records the halt of a feed that went stale, so its consumers are notified through `FeedHalted`.
+        pub fn report_stale(origin, feed_id: FeedId) -> result::Result<(), Error> {
+            ensure_signed(origin)?;
+            ensure!(<Oracles<T>>::exists(feed_id), Error::UnknownFeed);
+            ensure!(Self::is_stale(feed_id), Error::NotStale);
+            ensure!(!<Halted>::exists(feed_id), Error::AlreadyHalted);
+
+            Self::halt(feed_id, HaltReason::Stale);
+
+            Ok(())
+        }
+
+        fn commit_feed(
+            origin,
+            commit: OracleCommit,
+            signature: <T::AuthorityId as RuntimeAppPublic>::Signature
+        ) -> result::Result<(), Error> {
             ensure_none(origin)?;
-            support::print("update feed");
-            
-            if !<Results<T>>::exists(1) {
-                support::print("doesn't exist");
-                let mut o = OracleResult::default();
-                o.values = vec![message.value];
-                o.last_update = <timestamp::Module<T>>::now();
-                <Results<T>>::insert(1, o);
-                // <Results<T>>::insert(0, OracleResult { values: vec![value], last_update: <timestamp::Module<T>>::now() });
+
+            ensure!((commit.authority_index as usize) < Keys::<T>::get().len(), Error::UnknownAuthority);
+            let feed = Self::oracles(commit.feed_id).ok_or(Error::UnknownFeed)?;
+            ensure!(feed.commit_reveal, Error::NotCommitReveal);
+
+            let mut round = Self::current_round(commit.feed_id);
+            ensure!(commit.round == round.id, Error::WrongRound);
+            Self::check_spacing(commit.feed_id, &round)?;
+            ensure!(
+                !round.commits.iter().chain(&round.late_commits).any(|(index, _)| *index == commit.authority_index),
+                Error::AlreadySubmitted
+            );
+
+            if round.revealing {
+                round.late_commits.push((commit.authority_index, commit.commitment));
             } else {
-                support::print("exists");
-                <Results<T>>::mutate(1, |o| {
-                    if o.values.len() < 100 {
-                        o.values.push(message.value);
-                    } else {
-                        o.values.drain(0..1);
-                        o.values.push(message.value);
+                round.commits.push((commit.authority_index, commit.commitment));
+                if round.commits.len() >= Self::quorum() {
+                    round.revealing = true;
+                    let deadline = <system::Module<T>>::block_number() + T::RevealPeriod::get();
+                    <RevealDeadlines<T>>::insert(commit.feed_id, deadline);
+                    <RevealingFeeds>::mutate(|feeds| feeds.push(commit.feed_id));
+                    Self::deposit_event(RawEvent::RoundRevealing(commit.feed_id, round.id));
+                }
+            }
+            <Rounds>::insert(commit.feed_id, round);
+
+            Ok(())
+        }
+
+        fn update_feed(
+            origin,
+            message: OracleMessage,
+            signature: <T::AuthorityId as RuntimeAppPublic>::Signature
+        ) -> result::Result<(), Error> {
+            ensure_none(origin)?;
+
+            // The signature was checked against the keys in `validate_unsigned`, the set may have rotated since.
+            ensure!((message.authority_index as usize) < Keys::<T>::get().len(), Error::UnknownAuthority);
+            let feed = Self::oracles(message.feed_id).ok_or(Error::UnknownFeed)?;
+            ensure!(message.value.decimals() == feed.decimals, Error::WrongDecimals);
+            Self::check_attestation(&feed, &message)?;
+
+            let mut round = Self::current_round(message.feed_id);
+            match Self::report_kind(&feed, &round, &message)? {
+                ReportKind::Aggregate => {},
+                ReportKind::PreviousRound => {
+                    if let Some(ref mut previous) = round.previous {
+                        previous.reporters.push(message.authority_index);
                     }
-                    o.last_update = <timestamp::Module<T>>::now();
-                });
+                    <Rounds>::insert(message.feed_id, round);
+                    // The previous round's aggregate is still the latest one.
+                    let aggregate = Self::results(message.feed_id).latest;
+                    Self::record_reports(&[(message.authority_index, message.value)], aggregate);
+                    return Ok(());
+                },
+            }
+
+            round.submissions.push((message.authority_index, message.value));
+
+            if round.submissions.len() < Self::quorum() {
+                <Rounds>::insert(message.feed_id, round);
+                return Ok(());
+            }
+
+            let values = round.submissions.iter().map(|(_, value)| *value).collect::<Vec<_>>();
+            let aggregate = median(&values);
+
+            let now = <timestamp::Module<T>>::now();
+            let jumped = <Results<T>>::exists(message.feed_id)
+                && Self::deviates(aggregate, Self::results(message.feed_id).latest, feed.max_jump);
+            let mut history = Self::history(message.feed_id);
+            history.push(Observation { price: aggregate, timestamp: now });
+            // The last aggregate recorded before the period still held at its start.
+            let cutoff = now.saturating_sub(T::HistoryPeriod::get());
+            let expired = history.iter().skip(1).take_while(|observation| observation.timestamp <= cutoff).count();
+            history.drain(..expired);
+            let historic_median = median(&history.iter().map(|observation| observation.price).collect::<Vec<_>>());
+            <History<T>>::insert(message.feed_id, history);
+            <Results<T>>::insert(message.feed_id, OracleResult {
+                latest: aggregate,
+                round: round.id,
+                median: historic_median,
+                last_update: now,
+            });
+            let previous = PreviousRound {
+                reporters: round.submissions.iter().map(|(index, _)| *index).collect(),
+                commits: round.commits.iter().chain(&round.late_commits).cloned().collect(),
+            };
+            let next = Round {
+                id: round.id + 1,
+                session: round.session,
+                previous: Some(previous),
+                ..Default::default()
+            };
+            <Rounds>::insert(message.feed_id, next);
+            Self::stop_revealing(message.feed_id);
+            Self::record_round(message.feed_id, &round, aggregate);
+            Self::reward_round(message.feed_id, &round, aggregate);
+
+            Self::deposit_event(RawEvent::RoundFinalized(message.feed_id, round.id, aggregate));
+
+            // A jump halts the feed until the next round confirms the new price.
+            if jumped {
+                Self::halt(message.feed_id, HaltReason::PriceJump);
+            } else if <Halted>::take(message.feed_id).is_some() {
+                Self::deposit_event(RawEvent::FeedResumed(message.feed_id));
+            }
+
+            Ok(())
+        }
+
+        fn on_initialize(now: T::BlockNumber) {
+            for feed_id in Self::revealing_feeds() {
+                if Self::reveal_deadline(feed_id).map_or(true, |deadline| now > deadline) {
+                    Self::abandon_round(feed_id);
+                }
             }
         }
 
         // Runs after every block.
         fn offchain_worker(now: T::BlockNumber) {
-            support::print("Hello from the offchain worker!");
-            let value = Self::fetch("http://localhost:7666/mock");
-            match Self::do_update(value) {
-                Ok(_) => {},
-                Err(err) => support::print(err),
+            // Feeds are never removed, so every id below the next free one may be registered.
+            for feed_id in 0..<NextFreeId>::get() {
+                let feed = match Self::oracles(feed_id) {
+                    Some(feed) => feed,
+                    None => continue,
+                };
+
+                // Nodes only report on the feeds their operator configured an endpoint for.
+                let endpoint = match Self::endpoint(feed_id) {
+                    Ok(Some(endpoint)) => endpoint,
+                    Ok(None) => continue,
+                    Err(err) => {
+                        support::print(err);
+                        continue;
+                    }
+                };
+
+                let lock = match Self::acquire_lock(feed_id, now) {
+                    Some(lock) => lock,
+                    None => continue,
+                };
+
+                // Revealing needs no fresh price, only the one this node committed to.
+                if feed.commit_reveal && Self::current_round(feed_id).revealing {
+                    if let Err(err) = Self::do_reveal(feed_id) {
+                        support::print(err);
+                    }
+                    Self::release_lock(feed_id, lock);
+                    continue;
+                }
+
+                let result = Self::fetch(&endpoint)
+                    .and_then(|body| {
+                        let value = json::extract(&body, &feed.path)
+                            .and_then(|number| json::parse_decimal(number, feed.decimals))
+                            .ok_or(OffchainErr::DecodeResponse)?;
+                        let attestation = match feed.provider {
+                            Some(ref provider) => Some(Self::attestation(&body, provider)?),
+                            None => None,
+                        };
+                        Ok((value, attestation))
+                    })
+                    .and_then(|(value, attestation)| {
+                        Self::submit_if_due(feed_id, &feed, now, value, attestation)
+                    });
+                if let Err(err) = result {
+                    support::print(err);
+                }
+
+                Self::release_lock(feed_id, lock);
             }
         }
     }
 }
 
 impl<T: Trait> Module<T> {
-    fn new_id() -> u32 {
+    fn new_id() -> FeedId {
         <NextFreeId>::mutate(|n| { let r = *n; *n +=1; r })
     }
 
-    fn fetch(endpoint: &str) -> u32 {
-        let request_id = runtime_io::http_request_start("GET", endpoint, &[]).unwrap();
-        runtime_io::http_request_write_body(request_id, &[], None).unwrap_or(());
-        runtime_io::http_response_wait(&[request_id], None);
-        let buffer: &mut [u8] = &mut [0; 4];
-        let size = runtime_io::http_response_read_body(request_id, buffer, None).unwrap_or(42);
-        let mut result = &buffer[..];
-        u32::decode(&mut result).unwrap()
+    /// The endpoint configured for `feed_id` in this node's offchain local storage, if any.
+    fn endpoint(feed_id: FeedId) -> Result<Option<Endpoint>, OffchainErr> {
+        runtime_io::local_storage_get(StorageKind::PERSISTENT, &endpoint_key(feed_id))
+            .filter(|raw| !raw.is_empty())
+            .map(|raw| Endpoint::decode(&mut &raw[..]).map_err(|_| OffchainErr::DecodeEndpoint))
+            .transpose()
     }
 
-    fn do_update(value: u32) -> Result<(), OffchainErr> {
-        support::print("Here is the value!");
-        support::print(value as u64);
-        let authorities = Keys::<T>::get();
-        let mut local_keys = T::AuthorityId::all();
-        local_keys.sort();
-
-        for (authority_index, key) in authorities.into_iter()
-            .enumerate()
-            .filter_map(|(index, authority)| {
-                local_keys.binary_search(&authority)
-                    .ok()
-                    .map(|location| (index as u32, &local_keys[location]))
-            })
-        {
-            let oracle_message = OracleMessage {
-                value: value,
-                authority_index: authority_index,
-            };
-
-            let signature = key.sign(&oracle_message.encode()).ok_or(OffchainErr::FailedSigning)?;
-            let call = Call::update_feed(oracle_message, signature);
-            T::SubmitTransaction::submit_unsigned(call)
-                .map_err(|_| OffchainErr::SubmitTransaction)?;
+    /// Takes this node's worker lock for `feed_id`, unless a run that is still within its `LOCK_PERIOD` holds it.
+    /// Returns the lock's encoded expiry.
+    fn acquire_lock(feed_id: FeedId, now: T::BlockNumber) -> Option<Vec<u8>> {
+        let key = local_key(LOCK_KEY_PREFIX, feed_id);
+        let current = runtime_io::local_storage_get(StorageKind::PERSISTENT, &key);
+        let held_until = current.as_ref().and_then(|raw| T::BlockNumber::decode(&mut &raw[..]).ok());
+        if held_until.map_or(false, |expiry| expiry > now) {
+            return None;
         }
-        Ok(())
+
+        let lock = (now + T::BlockNumber::from(LOCK_PERIOD)).encode();
+        let acquired = runtime_io::local_storage_compare_and_set(
+            StorageKind::PERSISTENT,
+            &key,
+            current.as_ref().map(Vec::as_slice),
+            &lock,
+        );
+        if acquired { Some(lock) } else { None }
     }
 
-    fn initialize_keys(keys: &[T::AuthorityId]) {
-        if !keys.is_empty() {
-            assert!(Keys::<T>::get().is_empty(), "Keys are already initialized!");
-            Keys::<T>::put(keys);
-        }
+    /// Releases the worker lock for `feed_id`, unless it expired and another run took it since.
+    fn release_lock(feed_id: FeedId, lock: Vec<u8>) {
+        runtime_io::local_storage_compare_and_set(
+            StorageKind::PERSISTENT,
+            &local_key(LOCK_KEY_PREFIX, feed_id),
+            Some(&lock),
+            &T::BlockNumber::zero().encode(),
+        );
     }
-}
 
-impl<T: Trait> session::OneSessionHandler<T::AccountId> for Module<T> {
-    type Key = T::AuthorityId;
+    /// Submits `value` to the feed's current round if this node has not done so yet and the round is open, the
+    /// feed's update interval passed or the price deviates from the latest aggregate.
+    fn submit_if_due(
+        feed_id: FeedId,
+        feed: &FeedOf<T>,
+        now: T::BlockNumber,
+        value: Price,
+        attestation: Option<Attestation>,
+    ) -> Result<(), OffchainErr> {
+        let key = local_key(SUBMISSION_KEY_PREFIX, feed_id);
+        let last = runtime_io::local_storage_get(StorageKind::PERSISTENT, &key)
+            .and_then(|raw| LastSubmission::<T::BlockNumber>::decode(&mut &raw[..]).ok());
+        let round = Self::current_round(feed_id);
 
-    fn on_genesis_session<'a, I: 'a>(validators: I)
-        where I: Iterator<Item=(&'a T::AccountId, T::AuthorityId)>
-    {
-        let keys = validators.map(|x| x.1).collect::<Vec<_>>();
-        Self::initialize_keys(&keys);
-    }
+        let interval_passed = last.as_ref().map_or(true, |last| now >= last.block + feed.update_interval);
+        let already_submitted = last.as_ref().map_or(false, |last| last.round == round.id);
+        // A submission still waiting in the pool is only repeated once the interval passed.
+        if already_submitted && !interval_passed {
+            return Ok(());
+        }
 
-    fn on_new_session<'a, I: 'a>(_changed: bool, validators: I, _queued_validators: I)
-		where I: Iterator<Item=(&'a T::AccountId, T::AuthorityId)>
-	{
-		// Remember who the authorities are for the new session.
-		Keys::<T>::put(validators.map(|x| x.1).collect::<Vec<_>>());
-	}
+        let round_open = !round.submissions.is_empty() || !round.commits.is_empty();
+        // Nor is a round opened before the chain accepts it.
+        if Self::check_spacing(feed_id, &round).is_err() {
+            return Ok(());
+        }
+        let deviated = !<Results<T>>::exists(feed_id)
+            || Self::deviates(value, Self::results(feed_id).latest, feed.deviation);
+        if !round_open && !interval_passed && !deviated {
+            return Ok(());
+        }
 
-    fn on_before_session_ending() {
-        // ignore
+        if feed.commit_reveal {
+            Self::do_commit(feed_id, value, attestation)?;
+        } else {
+            Self::do_update(feed_id, value, attestation, None)?;
+        }
+        let submission = LastSubmission { block: now, round: round.id };
+        runtime_io::local_storage_set(StorageKind::PERSISTENT, &key, &submission.encode());
+        Ok(())
     }
 
-    fn on_disabled(_i: usize) {
-        // ignore
+    /// The provider's signature and timestamp in the endpoint's response.
+    fn attestation(body: &[u8], provider: &Provider) -> Result<Attestation, OffchainErr> {
+        let signature = json::extract(body, &provider.signature_path)
+            .and_then(json::decode_hex)
+            .ok_or(OffchainErr::DecodeAttestation)?;
+        let timestamp = json::extract(body, &provider.timestamp_path)
+            .and_then(|number| json::parse_decimal(number, 0))
+            .and_then(|timestamp| u64::try_from(timestamp.value()).ok())
+            .ok_or(OffchainErr::DecodeAttestation)?;
+        Ok(Attestation { timestamp, signature })
     }
-}
 
-impl<T: Trait> support::unsigned::ValidateUnsigned for Module<T> {
-    type Call = Call<T>;
+    /// The body of the endpoint's successful response, retrying transient failures up to `HTTP_RETRIES` times.
+    fn fetch(endpoint: &Endpoint) -> Result<Vec<u8>, OffchainErr> {
+        retry(|| Self::request(endpoint))
+    }
 
-    fn validate_unsigned(call: &Self::Call) -> TransactionValidity {
-        if let Call::update_feed(oracle_message, signature) = call {
+    /// A single request to the endpoint, which must complete within `HTTP_DEADLINE_MS`.
+    fn request(endpoint: &Endpoint) -> Result<Vec<u8>, OffchainErr> {
+        let deadline = runtime_io::timestamp().add(Duration::from_millis(HTTP_DEADLINE_MS));
+        let url = rstd::str::from_utf8(&endpoint.url).map_err(|_| OffchainErr::InvalidEndpoint)?;
+        let request_id = runtime_io::http_request_start("GET", url, &[])
+            .map_err(|_| OffchainErr::HttpRequest)?;
+        for (name, value) in &endpoint.headers {
+            let name = rstd::str::from_utf8(name).map_err(|_| OffchainErr::InvalidEndpoint)?;
+            let value = rstd::str::from_utf8(value).map_err(|_| OffchainErr::InvalidEndpoint)?;
+            runtime_io::http_request_add_header(request_id, name, value)
+                .map_err(|_| OffchainErr::HttpRequest)?;
+        }
+        runtime_io::http_request_write_body(request_id, &[], Some(deadline)).map_err(http_error)?;
 
-            let current_session = <session::Module<T>>::current_index();
+        check_status(runtime_io::http_response_wait(&[request_id], Some(deadline)).pop())?;
 
-            let keys = Keys::<T>::get();
-            let authority_id = match keys.get(oracle_message.authority_index as usize) {
-                Some(id) => id,
-                None => return InvalidTransaction::BadProof.into(),
-            };
+        let mut body = Vec::new();
+        let mut buffer = [0; 1024];
+        loop {
+            let read = runtime_io::http_response_read_body(request_id, &mut buffer, Some(deadline))
+                .map_err(http_error)?;
+            if read == 0 {
+                return Ok(body);
+            }
+            body.extend_from_slice(&buffer[..read]);
+            if body.len() > MAX_RESPONSE_LENGTH {
+                return Err(OffchainErr::ResponseTooLarge);
+            }
+        }
+    }
+
+
+    /// The state of the feed registered under `feed_id` as served by `OracleApi`.
+    pub fn feed_status(feed_id: FeedId, twap_window: u64) -> Option<FeedStatusOf<T>> {
+        let feed = Self::oracles(feed_id)?;
+        let result = if <Results<T>>::exists(feed_id) { Some(Self::results(feed_id)) } else { None };
+        let now = <timestamp::Module<T>>::now();
+        let stale = result.as_ref().map_or(false, |result| now.saturating_sub(result.last_update) > feed.max_age);
+
+        Some(FeedStatus {
+            id: feed_id,
+            latest: result.as_ref().map(|result| result.latest),
+            median: result.as_ref().map(|result| result.median),
+            twap: <Self as OracleProvider>::twap(feed_id, twap_window),
+            last_update: result.map(|result| result.last_update),
+            stale,
+            halted: Self::halted(feed_id).or(if stale { Some(HaltReason::Stale) } else { None }),
+            feed,
+        })
+    }
+
+    /// Checks that a feed with a provider received a recent price signed by the provider.
+    fn check_attestation(feed: &FeedOf<T>, message: &OracleMessage) -> result::Result<(), Error> {
+        let provider = match feed.provider {
+            Some(ref provider) => provider,
+            None => return Ok(()),
+        };
+        let attestation = message.attestation.as_ref().ok_or(Error::MissingAttestation)?;
+
+        // Old signed prices must not be replayed, they are held to the same age as the feed's aggregates.
+        let now = <timestamp::Module<T>>::now().saturated_into::<u64>() / 1000;
+        let age = now.saturating_sub(attestation.timestamp).saturating_mul(1000);
+        ensure!(age <= feed.max_age.saturated_into::<u64>(), Error::StaleAttestation);
+        // A timestamp far ahead would otherwise keep the signed price fresh long after it was published.
+        ensure!(attestation.timestamp <= now.saturating_add(MAX_CLOCK_SKEW_SECS), Error::FutureAttestation);
+
+        let signed = attestation_message(
+            &provider.key,
+            message.feed_id,
+            &feed.description,
+            message.value,
+            attestation.timestamp,
+        );
+        ensure!(Self::is_attested(&provider.key, &signed, &attestation.signature), Error::InvalidAttestation);
+
+        Ok(())
+    }
+
+    /// How `message` counts towards the feed, whose current round is `round`.
+    fn report_kind(feed: &FeedOf<T>, round: &Round, message: &OracleMessage) -> result::Result<ReportKind, Error> {
+        let authority_index = message.authority_index;
+        if message.round != round.id {
+            let previous = match round.previous {
+                Some(ref previous) if message.round.checked_add(1) == Some(round.id) => previous,
+                _ => return Err(Error::WrongRound),
+            };
+            ensure!(!previous.reporters.contains(&authority_index), Error::AlreadySubmitted);
+            if feed.commit_reveal {
+                Self::check_reveal(&previous.commits, message)?;
+            }
+            return Ok(ReportKind::PreviousRound);
+        }
+
+        ensure!(
+            !round.submissions.iter().any(|(index, _)| *index == authority_index),
+            Error::AlreadySubmitted
+        );
+        if !feed.commit_reveal {
+            Self::check_spacing(message.feed_id, round)?;
+            return Ok(ReportKind::Aggregate);
+        }
+
+        ensure!(round.revealing, Error::CommitPhase);
+        let commits = round.commits.iter().chain(&round.late_commits).cloned().collect::<Vec<_>>();
+        Self::check_reveal(&commits, message)?;
+        Ok(ReportKind::Aggregate)
+    }
+
+    /// Checks that `round` of `feed_id` is open already, or may be opened as the feed's last round finalised at
+    /// least `MinRoundSpacing` ago.
+    fn check_spacing(feed_id: FeedId, round: &Round) -> result::Result<(), Error> {
+        if !round.submissions.is_empty() || !round.commits.is_empty() || !<Results<T>>::exists(feed_id) {
+            return Ok(());
+        }
+        let opens_at = Self::results(feed_id).last_update + T::MinRoundSpacing::get();
+        ensure!(<timestamp::Module<T>>::now() >= opens_at, Error::RoundTooSoon);
+
+        Ok(())
+    }
+
+    /// Checks that a reveal matches the authority's commitment among `commits`.
+    fn check_reveal(commits: &[(AuthIndex, H256)], message: &OracleMessage) -> result::Result<(), Error> {
+        let committed = commits.iter()
+            .find(|(index, _)| *index == message.authority_index)
+            .map(|(_, commitment)| *commitment)
+            .ok_or(Error::NoCommitment)?;
+        let salt = message.salt.as_ref().ok_or(Error::CommitmentMismatch)?;
+        let revealed = commitment(message.feed_id, message.round, message.authority_index, message.value, salt);
+        ensure!(revealed == committed, Error::CommitmentMismatch);
+
+        Ok(())
+    }
+
+    fn is_attested(key: &ProviderKey, payload: &[u8], signature: &[u8]) -> bool {
+        match key {
+            ProviderKey::Ed25519(public) => {
+                // `from_slice` panics on a length mismatch.
+                if signature.len() != 64 {
+                    return false;
+                }
+                runtime_io::ed25519_verify(&ed25519::Signature::from_slice(signature), payload, public)
+            }
+            ProviderKey::Secp256k1(address) => {
+                if signature.len() != 65 {
+                    return false;
+                }
+                let mut raw = [0u8; 65];
+                raw.copy_from_slice(signature);
+                match runtime_io::secp256k1_ecdsa_recover(&raw, &runtime_io::keccak_256(payload)) {
+                    Ok(public) => H160::from_slice(&runtime_io::keccak_256(&public)[12..]) == *address,
+                    Err(_) => false,
+                }
+            }
+        }
+    }
+
+    fn halt(feed_id: FeedId, reason: HaltReason) {
+        if <Halted>::get(feed_id) != Some(reason) {
+            <Halted>::insert(feed_id, reason);
+            Self::deposit_event(RawEvent::FeedHalted(feed_id, reason));
+        }
+    }
+
+    /// Whether the latest aggregate of `feed_id` is older than the feed's maximum age.
+    fn is_stale(feed_id: FeedId) -> bool {
+        match Self::oracles(feed_id) {
+            Some(feed) if <Results<T>>::exists(feed_id) => {
+                let now = <timestamp::Module<T>>::now();
+                now.saturating_sub(Self::results(feed_id).last_update) > feed.max_age
+            }
+            _ => false,
+        }
+    }
+
+    /// The round collecting submissions for `feed_id`. A round left over from an earlier session starts over with
+    /// the current session's keys, keeping its id.
+    fn current_round(feed_id: FeedId) -> Round {
+        let session = <session::Module<T>>::current_index();
+        let round = Self::rounds(feed_id);
+        if round.session != session {
+            return Round { id: round.id, session, ..Default::default() };
+        }
+        round
+    }
 
-            let signature_valid = oracle_message.using_encoded(|encoded_message| {
-                authority_id.verify(&encoded_message, &signature)
+    /// Starts the revealing round of `feed_id` over under the next id, counting it as missed by the authorities
+    /// that committed to it without revealing.
+    fn abandon_round(feed_id: FeedId) {
+        let round = Self::rounds(feed_id);
+        Self::stop_revealing(feed_id);
+
+        // Indices from an earlier session do not map to the current validators.
+        if round.session == <session::Module<T>>::current_index() {
+            let validators = <session::Module<T>>::validators();
+            <ReporterRecords<T>>::mutate(|records| {
+                let unrevealed = round.commits.iter().chain(&round.late_commits)
+                    .filter(|(index, _)| !round.submissions.iter().any(|(revealed, _)| revealed == index))
+                    .filter_map(|(index, _)| validators.get(*index as usize));
+                for validator in unrevealed {
+                    let record = Self::reporter_record(records, validator);
+                    record.rounds = record.rounds.saturating_add(1);
+                }
             });
+        }
+
+        <Rounds>::insert(feed_id, Round { id: round.id + 1, session: round.session, ..Default::default() });
+        Self::deposit_event(RawEvent::RoundAbandoned(feed_id, round.id));
+    }
+
+    /// Stops checking the round of `feed_id` for an expired reveal deadline.
+    fn stop_revealing(feed_id: FeedId) {
+        <RevealDeadlines<T>>::remove(feed_id);
+        <RevealingFeeds>::mutate(|feeds| feeds.retain(|revealing| *revealing != feed_id));
+    }
 
-            if !signature_valid {
-                return InvalidTransaction::BadProof.into();
+    /// Counts a finalised round of `feed_id` towards the rounds expected from every validator of the session that
+    /// did not skip the feed, and its reports towards the statistics of their authorities.
+    fn record_round(feed_id: FeedId, round: &Round, aggregate: Price) {
+        let validators = <session::Module<T>>::validators();
+        <ReporterRecords<T>>::mutate(|records| {
+            for validator in validators.iter().filter(|validator| !<SkippedFeeds<T>>::get(*validator, &feed_id)) {
+                let record = Self::reporter_record(records, validator);
+                record.rounds = record.rounds.saturating_add(1);
             }
+        });
 
-            Ok(ValidTransaction {
-                priority: 0,
-                requires: vec![],
-                provides: vec![(current_session, authority_id).encode()],
-                longevity: TransactionLongevity::max_value(),
-                propagate: true,
-            })
+        Self::record_reports(&round.submissions, aggregate);
+    }
+
+    /// Counts reports on a finalised round towards the statistics of their authorities' validators.
+    fn record_reports(reports: &[(AuthIndex, Price)], aggregate: Price) {
+        // The keys are stored in the order of the session's validators.
+        let validators = <session::Module<T>>::validators();
+        <ReporterRecords<T>>::mutate(|records| {
+            for (authority_index, value) in reports {
+                let validator = match validators.get(*authority_index as usize) {
+                    Some(validator) => validator,
+                    None => continue,
+                };
+                let record = Self::reporter_record(records, validator);
+                record.submissions = record.submissions.saturating_add(1);
+                if Self::is_outlier(*value, aggregate) {
+                    record.outliers = record.outliers.saturating_add(1);
+                }
+            }
+        });
+    }
+
+    /// The record of `validator` among `records`, added if it has none yet.
+    fn reporter_record<'a>(
+        records: &'a mut Vec<(T::ValidatorId, ReporterRecord)>,
+        validator: &T::ValidatorId,
+    ) -> &'a mut ReporterRecord {
+        let position = match records.iter().position(|(id, _)| id == validator) {
+            Some(position) => position,
+            None => {
+                records.push((validator.clone(), ReporterRecord::default()));
+                records.len() - 1
+            }
+        };
+        &mut records[position].1
+    }
+
+    /// Mints the round reward for the validators whose submissions to the round were not outliers, as far as the
+    /// session's reward budget allows.
+    fn reward_round(feed_id: FeedId, round: &Round, aggregate: Price) {
+        let validators = <session::Module<T>>::validators();
+        let rewarded = round.submissions.iter()
+            .filter(|(_, value)| !Self::is_outlier(*value, aggregate))
+            .filter_map(|(authority_index, _)| validators.get(*authority_index as usize))
+            .map(|validator| T::RewardAccount::convert(validator.clone()))
+            .collect::<Vec<_>>();
+        if rewarded.is_empty() {
+            return;
+        }
+
+        let budget = T::MaxSessionReward::get().saturating_sub(Self::session_reward());
+        let count = BalanceOf::<T>::from(rewarded.len() as u32);
+        let reward = T::RoundReward::get().min(budget) / count;
+        if reward.is_zero() {
+            return;
+        }
+        <SessionReward<T>>::mutate(|minted| *minted = minted.saturating_add(reward * count));
+        for account in rewarded {
+            // Dropping the positive imbalance raises the total issuance by the reward.
+            let _ = T::Currency::deposit_creating(&account, reward);
+            Self::deposit_event(RawEvent::OracleRewarded(feed_id, round.id, account, reward));
+        }
+    }
+
+    /// Whether `value` deviates from the round's `aggregate` by more than `OutlierDeviation`.
+    fn is_outlier(value: Price, aggregate: Price) -> bool {
+        Self::deviates(value, aggregate, T::OutlierDeviation::get())
+    }
+
+    /// Whether `value` deviates from `reference`, a price of the same feed, by more than `threshold` of it.
+    fn deviates(value: Price, reference: Price, threshold: Perbill) -> bool {
+        let deviation = if value.value() > reference.value() {
+            value.value() - reference.value()
         } else {
-            InvalidTransaction::Call.into()
+            reference.value() - value.value()
+        };
+        deviation > threshold * reference.value()
+    }
+
+    /// Once the ending session closes a participation period, reports the validators that missed too many of the
+    /// period's rounds expected from them or submitted too many outliers, and clears the period's statistics.
+    fn report_misreporting() {
+        let session_index = <session::Module<T>>::current_index();
+        if (session_index + 1) % T::ParticipationPeriod::get().max(1) != 0 {
+            return;
+        }
+        let authorities = Keys::<T>::get().len() as u32;
+
+        let offenders = <ReporterRecords<T>>::take().into_iter()
+            .filter(|(_, record)| {
+                record.submissions < T::MinParticipation::get() * record.rounds
+                    || record.outliers > T::MaxOutliers::get() * record.submissions
+            })
+            .filter_map(|(id, _)| {
+                T::FullIdentificationOf::convert(id.clone()).map(|full_id| (id, full_id))
+            })
+            .collect::<Vec<IdentificationTuple<T>>>();
+        if offenders.is_empty() {
+            return;
+        }
+
+        let offence = MisreportingOffence {
+            session_index,
+            validator_set_count: authorities,
+            offenders,
+        };
+        T::ReportMisreporting::report_offence(vec![], offence);
+    }
+
+    /// The number of submissions that finalise a round, never less than one.
+    fn quorum() -> usize {
+        let keys = Keys::<T>::get().len() as u32;
+        (T::Quorum::get() * keys).max(1) as usize
+    }
+
+    /// The current authorities whose keys this node holds, with their indices into the set.
+    fn local_authorities() -> Vec<(AuthIndex, T::AuthorityId)> {
+        let authorities = Keys::<T>::get();
+        let mut local_keys = T::AuthorityId::all();
+        local_keys.sort();
+
+        authorities.into_iter()
+            .enumerate()
+            .filter_map(|(index, authority)| {
+                local_keys.binary_search(&authority)
+                    .ok()
+                    .map(|location| (index as u32, local_keys[location].clone()))
+            })
+            .collect()
+    }
+
+    fn do_update(
+        feed_id: FeedId,
+        value: Price,
+        attestation: Option<Attestation>,
+        salt: Option<H256>,
+    ) -> Result<(), OffchainErr> {
+        let round = Self::current_round(feed_id);
+
+        for (authority_index, key) in Self::local_authorities().into_iter()
+            .filter(|(index, _)| !round.submissions.iter().any(|(submitted, _)| submitted == index))
+            // Only the authorities that committed can reveal.
+            .filter(|(index, _)| {
+                let mut commits = round.commits.iter().chain(&round.late_commits);
+                salt.is_none() || commits.any(|(committed, _)| committed == index)
+            })
+        {
+            let oracle_message = OracleMessage {
+                feed_id: feed_id,
+                round: round.id,
+                value: value,
+                attestation: attestation.clone(),
+                salt: salt,
+                authority_index: authority_index,
+            };
+
+            let signature = key.sign(&oracle_message.encode()).ok_or(OffchainErr::FailedSigning)?;
+            let call = Call::update_feed(oracle_message, signature);
+            T::SubmitTransaction::submit_unsigned(call)
+                .map_err(|_| OffchainErr::SubmitTransaction)?;
+        }
+        Ok(())
+    }
+
+    /// Commits this node's authorities to `value` for the feed's current round, keeping the value and its salt
+    /// in offchain local storage until they are revealed. Once this node committed to the round, the remaining
+    /// authorities commit to the stored value and salt, so every commitment matches what is revealed.
+    fn do_commit(feed_id: FeedId, value: Price, attestation: Option<Attestation>) -> Result<(), OffchainErr> {
+        let round = Self::current_round(feed_id);
+        let committed = round.commits.iter().chain(&round.late_commits).map(|(index, _)| *index).collect::<Vec<_>>();
+        let uncommitted = Self::local_authorities().into_iter()
+            .filter(|(index, _)| !committed.contains(index))
+            .collect::<Vec<_>>();
+        if uncommitted.is_empty() {
+            return Ok(());
+        }
+
+        let storage_key = local_key(COMMIT_KEY_PREFIX, feed_id);
+        let stored = runtime_io::local_storage_get(StorageKind::PERSISTENT, &storage_key)
+            .and_then(|raw| PendingCommit::decode(&mut &raw[..]).ok())
+            .filter(|pending| pending.round == round.id);
+        let pending = match stored {
+            Some(pending) => pending,
+            None => {
+                let salt = H256(runtime_io::random_seed());
+                let pending = PendingCommit { round: round.id, value, salt, attestation };
+                runtime_io::local_storage_set(StorageKind::PERSISTENT, &storage_key, &pending.encode());
+                pending
+            }
+        };
+
+        for (authority_index, key) in uncommitted {
+            let commit = OracleCommit {
+                feed_id,
+                round: round.id,
+                commitment: commitment(feed_id, round.id, authority_index, pending.value, &pending.salt),
+                authority_index,
+            };
+
+            let signature = key.sign(&commit.encode()).ok_or(OffchainErr::FailedSigning)?;
+            let call = Call::commit_feed(commit, signature);
+            T::SubmitTransaction::submit_unsigned(call)
+                .map_err(|_| OffchainErr::SubmitTransaction)?;
+        }
+        Ok(())
+    }
+
+    /// Reveals the value this node committed to for the feed's current round, if it committed to it.
+    fn do_reveal(feed_id: FeedId) -> Result<(), OffchainErr> {
+        let key = local_key(COMMIT_KEY_PREFIX, feed_id);
+        let pending = match runtime_io::local_storage_get(StorageKind::PERSISTENT, &key)
+            .and_then(|raw| PendingCommit::decode(&mut &raw[..]).ok())
+        {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+        if pending.round != Self::current_round(feed_id).id {
+            return Ok(());
+        }
+
+        Self::do_update(feed_id, pending.value, pending.attestation, Some(pending.salt))
+    }
+
+    /// Validates an authority's signed report of kind `kind` on a round of a feed, which the caller checked takes
+    /// it. Each authority may report once per kind, round and feed in a session.
+    fn validate_report(
+        authority_index: AuthIndex,
+        feed_id: FeedId,
+        round: RoundId,
+        encoded: &[u8],
+        signature: &<T::AuthorityId as RuntimeAppPublic>::Signature,
+        kind: &[u8],
+    ) -> TransactionValidity {
+        let current_session = <session::Module<T>>::current_index();
+
+        let keys = Keys::<T>::get();
+        let authority_id = match keys.get(authority_index as usize) {
+            Some(id) => id,
+            None => return InvalidTransaction::BadProof.into(),
+        };
+
+        if !<Oracles<T>>::exists(feed_id) {
+            return InvalidTransaction::Call.into();
+        }
+
+        if !authority_id.verify(&encoded, signature) {
+            return InvalidTransaction::BadProof.into();
+        }
+
+        Ok(ValidTransaction {
+            priority: 0,
+            requires: vec![],
+            provides: vec![(kind, current_session, authority_id, feed_id, round).encode()],
+            longevity: TransactionLongevity::max_value(),
+            propagate: true,
+        })
+    }
+
+    fn initialize_keys(keys: &[T::AuthorityId]) {
+        if !keys.is_empty() {
+            assert!(Keys::<T>::get().is_empty(), "Keys are already initialized!");
+            Keys::<T>::put(keys);
+        }
+    }
+}
+
+impl<T: Trait> OracleProvider for Module<T> {
+    fn median(feed: FeedId) -> Option<Price> {
+        if !<Results<T>>::exists(feed) {
+            return None;
+        }
+        Some(Self::results(feed).median)
+    }
+
+    fn latest(feed: FeedId) -> Option<Price> {
+        if !<Results<T>>::exists(feed) {
+            return None;
+        }
+        Some(Self::results(feed).latest)
+    }
+
+    fn twap(feed: FeedId, window: u64) -> Option<Price> {
+        let now = <timestamp::Module<T>>::now();
+        let start = now.saturating_sub(window.saturated_into::<T::Moment>());
+        let observations = Self::history(feed).into_iter()
+            .map(|observation| Observation {
+                price: observation.price,
+                timestamp: observation.timestamp.saturated_into::<u64>(),
+            })
+            .collect::<Vec<_>>();
+        time_weighted_average(&observations, start.saturated_into::<u64>(), now.saturated_into::<u64>())
+    }
+
+    fn is_halted(feed: FeedId) -> bool {
+        <Halted>::exists(feed) || Self::is_stale(feed)
+    }
+}
+
+/// Runs `request` until it succeeds, fails for a reason that is not transient, or failed `HTTP_RETRIES` more times.
+fn retry(mut request: impl FnMut() -> Result<Vec<u8>, OffchainErr>) -> Result<Vec<u8>, OffchainErr> {
+    let mut retries = 0;
+    loop {
+        match request() {
+            Err(err) => {
+                if !err.is_transient() || retries == HTTP_RETRIES {
+                    return Err(err);
+                }
+                support::print(err);
+                retries += 1;
+            }
+            body => return body,
+        }
+    }
+}
+
+/// Whether a request finished successfully, by the status `http_response_wait` reported for it.
+fn check_status(status: Option<HttpRequestStatus>) -> Result<(), OffchainErr> {
+    match status {
+        Some(HttpRequestStatus::Finished(code)) if code >= 200 && code < 300 => Ok(()),
+        Some(HttpRequestStatus::Finished(code)) => Err(OffchainErr::HttpStatus(code)),
+        Some(HttpRequestStatus::DeadlineReached) => Err(OffchainErr::HttpTimeout),
+        _ => Err(OffchainErr::HttpIo),
+    }
+}
+
+fn http_error(err: HttpError) -> OffchainErr {
+    match err {
+        HttpError::DeadlineReached => OffchainErr::HttpTimeout,
+        _ => OffchainErr::HttpIo,
+    }
+}
+
+/// The average of the prices observed between `start` and `now`, each weighted by how long it held until the next
+/// observation, rounding down. `None` without observations, or if the window starts before the first one, as the
+/// price before it is unknown.
+///
+/// The observations are ordered oldest first and are prices of the same feed, sharing its decimals.
+fn time_weighted_average(observations: &[Observation<u64>], start: u64, now: u64) -> Option<Price> {
+    if observations.first()?.timestamp > start {
+        return None;
+    }
+    let latest = observations.last()?.price;
+    if now <= start {
+        return Some(latest);
+    }
+
+    let mut weighted: u128 = 0;
+    let mut end = now;
+    for observation in observations.iter().rev() {
+        let from = observation.timestamp.max(start);
+        if from < end {
+            weighted = weighted.saturating_add(observation.price.value().saturating_mul((end - from) as u128));
         }
+        if observation.timestamp <= start {
+            break;
+        }
+        end = observation.timestamp;
+    }
+
+    Some(latest.with_value(weighted / (now - start) as u128))
+}
+
+/// The median of `values`, averaging the middle pair of an even count and rounding down. Zero for no values.
+///
+/// All values are prices of the same feed and so share its decimals.
+fn median(values: &[Price]) -> Price {
+    if values.is_empty() {
+        return Price::default();
+    }
+
+    let mut sorted = values.to_vec();
+    sorted.sort_by_key(Price::value);
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 0 {
+        let (low, high) = (sorted[mid - 1].value(), sorted[mid].value());
+        let average = low / 2 + high / 2 + (low % 2 + high % 2) / 2;
+        sorted[mid].with_value(average)
+    } else {
+        sorted[mid]
+    }
+}
+
+impl<T: Trait> session::OneSessionHandler<T::AccountId> for Module<T> {
+    type Key = T::AuthorityId;
+
+    fn on_genesis_session<'a, I: 'a>(validators: I)
+        where I: Iterator<Item=(&'a T::AccountId, T::AuthorityId)>
+    {
+        let keys = validators.map(|x| x.1).collect::<Vec<_>>();
+        Self::initialize_keys(&keys);
+    }
+
+    fn on_new_session<'a, I: 'a>(_changed: bool, validators: I, _queued_validators: I)
+		where I: Iterator<Item=(&'a T::AccountId, T::AuthorityId)>
+	{
+		// Remember who the authorities are for the new session. Open rounds start over with them when next read.
+		Keys::<T>::put(validators.map(|x| x.1).collect::<Vec<_>>());
+	}
+
+    fn on_before_session_ending() {
+        // Rounds start over with the next session's keys, those still taking reveals count as missed by their
+        // committed authorities that did not reveal.
+        for feed_id in Self::revealing_feeds() {
+            Self::abandon_round(feed_id);
+        }
+        Self::report_misreporting();
+        <SessionReward<T>>::kill();
+    }
+
+    fn on_disabled(_i: usize) {
+        // ignore
+    }
+}
+
+impl<T: Trait> support::unsigned::ValidateUnsigned for Module<T> {
+    type Call = Call<T>;
+
+    fn validate_unsigned(call: &Self::Call) -> TransactionValidity {
+        match call {
+            Call::update_feed(oracle_message, signature) => {
+                if let Some(feed) = Self::oracles(oracle_message.feed_id) {
+                    if Self::check_attestation(&feed, oracle_message).is_err() {
+                        return InvalidTransaction::BadProof.into();
+                    }
+                    // Reports that arrived after quorum stay valid while the next round is open.
+                    match Self::report_kind(&feed, &Self::current_round(oracle_message.feed_id), oracle_message) {
+                        Ok(_) => {},
+                        Err(Error::WrongRound) | Err(Error::AlreadySubmitted) | Err(Error::RoundTooSoon) => {
+                            return InvalidTransaction::Stale.into();
+                        },
+                        Err(_) => return InvalidTransaction::BadProof.into(),
+                    }
+                }
+
+                Self::validate_report(
+                    oracle_message.authority_index,
+                    oracle_message.feed_id,
+                    oracle_message.round,
+                    &oracle_message.encode(),
+                    signature,
+                    b"reveal",
+                )
+            }
+            Call::commit_feed(commit, signature) => {
+                let round = Self::current_round(commit.feed_id);
+                if commit.round != round.id || Self::check_spacing(commit.feed_id, &round).is_err() {
+                    return InvalidTransaction::Stale.into();
+                }
+
+                Self::validate_report(
+                    commit.authority_index,
+                    commit.feed_id,
+                    commit.round,
+                    &commit.encode(),
+                    signature,
+                    b"commit",
+                )
+            }
+            _ => InvalidTransaction::Call.into(),
+        }
+    }
+}
+
+/// An offence committed by oracle authorities that missed too many rounds or reported too many outliers during a
+/// participation period.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct MisreportingOffence<Offender> {
+    /// The last session of the participation period the offence was committed in.
+    session_index: SessionIndex,
+    /// The size of the validator set in that session.
+    validator_set_count: u32,
+    /// The authorities that misreported.
+    offenders: Vec<Offender>,
+}
+
+impl<Offender: Clone> Offence<Offender> for MisreportingOffence<Offender> {
+    const ID: Kind = *b"oracle:misreport";
+    type TimeSlot = SessionIndex;
+
+    fn offenders(&self) -> Vec<Offender> {
+        self.offenders.clone()
+    }
+
+    fn session_index(&self) -> SessionIndex {
+        self.session_index
+    }
+
+    fn validator_set_count(&self) -> u32 {
+        self.validator_set_count
+    }
+
+    fn time_slot(&self) -> Self::TimeSlot {
+        self.session_index
+    }
+
+    /// Up to 10%, in proportion to the share of the validator set that misreported: a lone authority with a bad
+    /// data source loses little, a group misreporting together loses a lot.
+    fn slash_fraction(offenders_count: u32, validator_set_count: u32) -> Perbill {
+        Perbill::from_rational_approximation(offenders_count, validator_set_count.saturating_mul(10))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use primitives::{Blake2Hasher, Pair};
+    use primitives::offchain::testing::TestOffchainExt;
+    use runtime_io::with_externalities;
+    use sr_primitives::testing::{Header, TestXt, UintAuthorityId};
+    use sr_primitives::traits::{BlakeTwo256, ConvertInto, IdentityLookup, OnInitialize};
+    use sr_primitives::weights::Weight;
+    use support::{assert_ok, assert_noop, impl_outer_dispatch, impl_outer_origin, parameter_types};
+
+    impl_outer_origin! {
+        pub enum Origin for Test {}
+    }
+
+    impl_outer_dispatch! {
+        pub enum Call for Test where origin: Origin {
+            oracle::Oracle,
+        }
+    }
+
+    #[derive(Clone, Eq, PartialEq)]
+    pub struct Test;
+    parameter_types! {
+        pub const BlockHashCount: u64 = 250;
+        pub const MaximumBlockWeight: Weight = 1024;
+        pub const MaximumBlockLength: u32 = 2 * 1024;
+        pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+    }
+    impl system::Trait for Test {
+        type Origin = Origin;
+        type Call = Call;
+        type Index = u64;
+        type BlockNumber = u64;
+        type Hash = H256;
+        type Hashing = BlakeTwo256;
+        type AccountId = u64;
+        type Lookup = IdentityLookup<Self::AccountId>;
+        type Header = Header;
+        type WeightMultiplierUpdate = ();
+        type Event = ();
+        type BlockHashCount = BlockHashCount;
+        type MaximumBlockWeight = MaximumBlockWeight;
+        type MaximumBlockLength = MaximumBlockLength;
+        type AvailableBlockRatio = AvailableBlockRatio;
+        type Version = ();
+    }
+    parameter_types! {
+        pub const ExistentialDeposit: u64 = 0;
+        pub const TransferFee: u64 = 0;
+        pub const CreationFee: u64 = 0;
+        pub const TransactionBaseFee: u64 = 0;
+        pub const TransactionByteFee: u64 = 0;
+    }
+    impl balances::Trait for Test {
+        type Balance = u64;
+        type OnFreeBalanceZero = ();
+        type OnNewAccount = ();
+        type Event = ();
+        type TransactionPayment = ();
+        type DustRemoval = ();
+        type TransferPayment = ();
+        type ExistentialDeposit = ExistentialDeposit;
+        type TransferFee = TransferFee;
+        type CreationFee = CreationFee;
+        type TransactionBaseFee = TransactionBaseFee;
+        type TransactionByteFee = TransactionByteFee;
+        type WeightToFee = ConvertInto;
+    }
+    parameter_types! {
+        pub const MinimumPeriod: u64 = 1;
+    }
+    impl timestamp::Trait for Test {
+        type Moment = u64;
+        type OnTimestampSet = ();
+        type MinimumPeriod = MinimumPeriod;
+    }
+    pub struct TestOnSessionEnding;
+    impl session::OnSessionEnding<u64> for TestOnSessionEnding {
+        fn on_session_ending(_ending: SessionIndex, _will_apply_at: SessionIndex) -> Option<Vec<u64>> {
+            None
+        }
+    }
+    parameter_types! {
+        pub const Period: u64 = 10;
+        pub const Offset: u64 = 0;
+        pub const DisabledValidatorsThreshold: Perbill = Perbill::from_percent(33);
+    }
+    impl session::Trait for Test {
+        type ShouldEndSession = session::PeriodicSessions<Period, Offset>;
+        type OnSessionEnding = TestOnSessionEnding;
+        type SessionHandler = (Oracle,);
+        type ValidatorId = u64;
+        type ValidatorIdOf = ConvertInto;
+        type Keys = UintAuthorityId;
+        type Event = ();
+        type SelectInitialValidators = ();
+        type DisabledValidatorsThreshold = DisabledValidatorsThreshold;
+    }
+    impl session::historical::Trait for Test {
+        type FullIdentification = u64;
+        type FullIdentificationOf = ConvertInto;
+    }
+    thread_local! {
+        static OFFENCES: RefCell<Vec<MisreportingOffence<(u64, u64)>>> = RefCell::new(Vec::new());
+        static MIN_ROUND_SPACING: RefCell<u64> = RefCell::new(0);
+    }
+    pub struct MinRoundSpacing;
+    impl Get<u64> for MinRoundSpacing {
+        fn get() -> u64 {
+            MIN_ROUND_SPACING.with(|v| *v.borrow())
+        }
+    }
+    pub struct OffenceHandler;
+    impl ReportOffence<u64, (u64, u64), MisreportingOffence<(u64, u64)>> for OffenceHandler {
+        fn report_offence(_reporters: Vec<u64>, offence: MisreportingOffence<(u64, u64)>) {
+            OFFENCES.with(|offences| offences.borrow_mut().push(offence));
+        }
+    }
+    parameter_types! {
+        pub const OracleBond: u64 = 100;
+        pub const Quorum: Perbill = Perbill::from_percent(50);
+        pub const OutlierDeviation: Perbill = Perbill::from_percent(5);
+        pub const MaxOutliers: Perbill = Perbill::from_percent(25);
+        pub const MinParticipation: Perbill = Perbill::from_percent(50);
+        pub const ParticipationPeriod: SessionIndex = 2;
+        pub const RoundReward: u64 = 10;
+        pub const MaxSessionReward: u64 = 25;
+        pub const MinUpdateInterval: u64 = 1;
+        pub const RevealPeriod: u64 = 5;
+        pub const HistoryPeriod: u64 = 60_000;
+    }
+    impl Trait for Test {
+        type AuthorityId = UintAuthorityId;
+        type Event = ();
+        type Currency = Balances;
+        type OracleBond = OracleBond;
+        type Quorum = Quorum;
+        type OutlierDeviation = OutlierDeviation;
+        type MaxOutliers = MaxOutliers;
+        type MinParticipation = MinParticipation;
+        type ParticipationPeriod = ParticipationPeriod;
+        type RoundReward = RoundReward;
+        type MaxSessionReward = MaxSessionReward;
+        type MinUpdateInterval = MinUpdateInterval;
+        type RevealPeriod = RevealPeriod;
+        type HistoryPeriod = HistoryPeriod;
+        type MinRoundSpacing = MinRoundSpacing;
+        type RewardAccount = ConvertInto;
+        type ReportMisreporting = OffenceHandler;
+        type Call = Call;
+        type SubmitTransaction = system::offchain::TransactionSubmitter<(), Call, TestXt<Call, ()>>;
+    }
+    type Oracle = Module<Test>;
+    type Balances = balances::Module<Test>;
+
+    const OWNER: u64 = 10;
+
+    // Four validators, 1 to 4, whose oracle keys are their ids and whose authority indices are one less.
+    fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
+        let mut t = system::GenesisConfig::default().build_storage::<Test>().unwrap();
+        balances::GenesisConfig::<Test> {
+            balances: vec![(OWNER, 1000)],
+            vesting: vec![],
+        }.assimilate_storage(&mut t).unwrap();
+        session::GenesisConfig::<Test> {
+            keys: (1..=4).map(|validator| (validator, UintAuthorityId(validator))).collect(),
+        }.assimilate_storage(&mut t).unwrap();
+        t.into()
+    }
+
+    fn register_feed() -> FeedId {
+        let feed_id = <NextFreeId>::get();
+        assert_ok!(Oracle::register_new_oracle(
+            Origin::signed(OWNER),
+            b"BTC/USD".to_vec(),
+            b"data.price".to_vec(),
+            2,
+            1,
+            Perbill::from_percent(1),
+            60_000,
+            Perbill::from_percent(50),
+        ));
+        feed_id
+    }
+
+    fn message(feed_id: FeedId, round: RoundId, authority_index: AuthIndex, value: u128) -> OracleMessage {
+        OracleMessage {
+            feed_id,
+            round,
+            value: Price::new(value, 2).unwrap(),
+            attestation: None,
+            salt: None,
+            authority_index,
+        }
+    }
+
+    fn submit(message: OracleMessage) -> result::Result<(), Error> {
+        let signature = UintAuthorityId(message.authority_index as u64 + 1).sign(&message.encode()).unwrap();
+        Oracle::update_feed(system::RawOrigin::None.into(), message, signature)
+    }
+
+    fn report(feed_id: FeedId, round: RoundId, authority_index: AuthIndex, value: u128) -> result::Result<(), Error> {
+        submit(message(feed_id, round, authority_index, value))
+    }
+
+    fn commit(feed_id: FeedId, round: RoundId, authority_index: AuthIndex, value: u128) -> result::Result<(), Error> {
+        let value = Price::new(value, 2).unwrap();
+        let commit = OracleCommit {
+            feed_id,
+            round,
+            commitment: commitment(feed_id, round, authority_index, value, &salt(authority_index)),
+            authority_index,
+        };
+        let signature = UintAuthorityId(authority_index as u64 + 1).sign(&commit.encode()).unwrap();
+        Oracle::commit_feed(system::RawOrigin::None.into(), commit, signature)
+    }
+
+    fn reveal(feed_id: FeedId, round: RoundId, authority_index: AuthIndex, value: u128) -> result::Result<(), Error> {
+        let mut message = message(feed_id, round, authority_index, value);
+        message.salt = Some(salt(authority_index));
+        submit(message)
+    }
+
+    fn salt(authority_index: AuthIndex) -> H256 {
+        H256::repeat_byte(authority_index as u8 + 1)
+    }
+
+    fn commit_reveal_feed() -> FeedId {
+        let feed_id = register_feed();
+        assert_ok!(Oracle::set_commit_reveal(Origin::signed(OWNER), feed_id, true));
+        feed_id
+    }
+
+    fn observation(price: u128, timestamp: u64) -> Observation<u64> {
+        Observation { price: Price::new(price, 2).unwrap(), timestamp }
+    }
+
+    #[test]
+    fn retries_only_transient_failures() {
+        let run = |mut results: Vec<Result<Vec<u8>, OffchainErr>>| {
+            let mut requests = 0;
+            let result = retry(|| {
+                requests += 1;
+                results.remove(0)
+            });
+            (result, requests)
+        };
+
+        assert_eq!(run(vec![Ok(b"42".to_vec())]), (Ok(b"42".to_vec()), 1));
+        assert_eq!(
+            run(vec![Err(OffchainErr::HttpTimeout), Err(OffchainErr::HttpStatus(503)), Ok(b"42".to_vec())]),
+            (Ok(b"42".to_vec()), 3)
+        );
+        // The first request and `HTTP_RETRIES` more.
+        assert_eq!(
+            run(vec![Err(OffchainErr::HttpIo), Err(OffchainErr::HttpIo), Err(OffchainErr::HttpTimeout)]),
+            (Err(OffchainErr::HttpTimeout), 3)
+        );
+        assert_eq!(run(vec![Err(OffchainErr::HttpStatus(404))]), (Err(OffchainErr::HttpStatus(404)), 1));
+        // Rate limited requests wait for a later block.
+        assert_eq!(run(vec![Err(OffchainErr::HttpStatus(429))]), (Err(OffchainErr::HttpStatus(429)), 1));
+        assert_eq!(run(vec![Err(OffchainErr::ResponseTooLarge)]), (Err(OffchainErr::ResponseTooLarge), 1));
+    }
+
+    #[test]
+    fn maps_response_status_and_deadlines() {
+        assert_eq!(check_status(Some(HttpRequestStatus::Finished(200))), Ok(()));
+        assert_eq!(check_status(Some(HttpRequestStatus::Finished(204))), Ok(()));
+        assert_eq!(check_status(Some(HttpRequestStatus::Finished(301))), Err(OffchainErr::HttpStatus(301)));
+        assert_eq!(check_status(Some(HttpRequestStatus::Finished(500))), Err(OffchainErr::HttpStatus(500)));
+        assert_eq!(check_status(Some(HttpRequestStatus::DeadlineReached)), Err(OffchainErr::HttpTimeout));
+        assert_eq!(check_status(Some(HttpRequestStatus::IoError)), Err(OffchainErr::HttpIo));
+        assert_eq!(check_status(None), Err(OffchainErr::HttpIo));
+        assert_eq!(http_error(HttpError::DeadlineReached), OffchainErr::HttpTimeout);
+        assert_eq!(http_error(HttpError::IoError), OffchainErr::HttpIo);
+    }
+
+    #[test]
+    fn twap_weights_prices_by_how_long_they_held() {
+        let observations = [observation(1000, 0), observation(2000, 60), observation(4000, 90)];
+
+        // 2000 held for 30 and 4000 for 10 of the last 40.
+        assert_eq!(time_weighted_average(&observations, 60, 100), Some(Price::new(2500, 2).unwrap()));
+        // 1000 held for 10 of the last 50, a window starting between observations.
+        assert_eq!(time_weighted_average(&observations, 50, 100), Some(Price::new(2200, 2).unwrap()));
+        // The whole window, starting at the first observation.
+        assert_eq!(time_weighted_average(&observations, 0, 100), Some(Price::new(1600, 2).unwrap()));
+        // A window reaching past the first observation has no price for its start.
+        assert_eq!(time_weighted_average(&observations[1..], 50, 100), None);
+        assert_eq!(time_weighted_average(&observations[..1], 0, 0), Some(Price::new(1000, 2).unwrap()));
+        assert_eq!(time_weighted_average(&[], 0, 100), None);
+    }
+
+    #[test]
+    fn participation_is_expected_on_every_round_of_the_period_unless_skipped() {
+        with_externalities(&mut new_test_ext(), || {
+            let feed_id = register_feed();
+            for round in 0..4 {
+                // The quorum of two finalises the round.
+                assert_ok!(report(feed_id, round, 0, 100));
+                assert_ok!(report(feed_id, round, 1, 100));
+                assert_eq!(Oracle::rounds(feed_id).id, round + 1);
+
+                // The fourth only reports after quorum, the third never does.
+                assert_ok!(report(feed_id, round, 3, 100));
+            }
+            assert_noop!(report(feed_id, 3, 3, 100), Error::AlreadySubmitted);
+            assert_noop!(report(feed_id, 2, 2, 100), Error::WrongRound);
+
+            // The fourth opts out of the second feed, its rounds are not expected from it.
+            let other_feed = register_feed();
+            assert_noop!(Oracle::skip_feed(Origin::signed(4), other_feed + 1, true), Error::UnknownFeed);
+            assert_ok!(Oracle::skip_feed(Origin::signed(4), other_feed, true));
+            assert!(Oracle::skipped_feeds(&4, &other_feed));
+            for round in 0..2 {
+                assert_ok!(report(other_feed, round, 0, 100));
+                assert_ok!(report(other_feed, round, 1, 100));
+            }
+
+            let offenders = || OFFENCES.with(|offences| {
+                offences.borrow().iter()
+                    .map(|offence| (offence.session_index, offence.offenders.clone()))
+                    .collect::<Vec<_>>()
+            });
+            // The period spans two sessions.
+            session::Module::<Test>::rotate_session();
+            assert!(offenders().is_empty());
+            session::Module::<Test>::rotate_session();
+            assert_eq!(offenders(), vec![(1, vec![(3, 3)])]);
+            assert!(Oracle::reporter_records().is_empty());
+        });
+    }
+
+    #[test]
+    fn rewards_are_capped_per_session() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_noop!(
+                Oracle::register_new_oracle(
+                    Origin::signed(OWNER),
+                    b"BTC/USD".to_vec(),
+                    b"data.price".to_vec(),
+                    2,
+                    0,
+                    Perbill::from_percent(1),
+                    60_000,
+                    Perbill::from_percent(50),
+                ),
+                Error::UpdateIntervalTooShort
+            );
+
+            let feed_id = register_feed();
+            let finalize_round = |round| {
+                assert_ok!(report(feed_id, round, 0, 100));
+                assert_ok!(report(feed_id, round, 1, 100));
+            };
+
+            // Two rounds mint their full reward, the third what is left of the budget, split evenly.
+            for round in 0..4 {
+                finalize_round(round);
+            }
+            assert_eq!(Balances::free_balance(&1), 12);
+            assert_eq!(Balances::free_balance(&2), 12);
+            assert_eq!(Oracle::session_reward(), 24);
+
+            <Oracle as session::OneSessionHandler<u64>>::on_before_session_ending();
+            finalize_round(4);
+            assert_eq!(Balances::free_balance(&1), 17);
+        });
+    }
+
+    #[test]
+    fn stale_feeds_halt_and_rounds_reset_when_read() {
+        with_externalities(&mut new_test_ext(), || {
+            let feed_id = register_feed();
+            assert_ok!(report(feed_id, 0, 0, 100));
+            assert_ok!(report(feed_id, 0, 1, 100));
+            assert!(!<Oracle as OracleProvider>::is_halted(feed_id));
+
+            timestamp::Module::<Test>::set_timestamp(60_001);
+            assert!(<Oracle as OracleProvider>::is_halted(feed_id));
+            assert_eq!(Oracle::feed_status(feed_id, 0).unwrap().halted, Some(HaltReason::Stale));
+
+            // A submission indexed into the old session's keys does not carry over.
+            assert_ok!(report(feed_id, 1, 0, 100));
+            session::Module::<Test>::rotate_session();
+            let round = Oracle::current_round(feed_id);
+            assert_eq!((round.id, round.submissions.len(), round.previous), (1, 0, None));
+            assert_ok!(report(feed_id, 1, 0, 100));
+
+            // A round within the maximum age resumes the feed.
+            assert_ok!(report(feed_id, 1, 1, 100));
+            assert!(!<Oracle as OracleProvider>::is_halted(feed_id));
+        });
+    }
+
+    #[test]
+    fn anyone_can_record_a_stale_feed_as_halted() {
+        with_externalities(&mut new_test_ext(), || {
+            let feed_id = register_feed();
+            assert_noop!(Oracle::report_stale(Origin::signed(5), feed_id + 1), Error::UnknownFeed);
+            assert_ok!(report(feed_id, 0, 0, 100));
+            assert_ok!(report(feed_id, 0, 1, 100));
+            assert_noop!(Oracle::report_stale(Origin::signed(5), feed_id), Error::NotStale);
+
+            timestamp::Module::<Test>::set_timestamp(60_001);
+            assert_ok!(Oracle::report_stale(Origin::signed(5), feed_id));
+            assert_eq!(Oracle::halted(feed_id), Some(HaltReason::Stale));
+            assert_noop!(Oracle::report_stale(Origin::signed(5), feed_id), Error::AlreadyHalted);
+
+            // The next round within the maximum age resumes the feed.
+            assert_ok!(report(feed_id, 1, 0, 100));
+            assert_ok!(report(feed_id, 1, 1, 100));
+            assert_eq!(Oracle::halted(feed_id), None);
+            assert!(!<Oracle as OracleProvider>::is_halted(feed_id));
+        });
+    }
+
+    fn attested(feed_id: FeedId, value: u128, timestamp: u64, signature: Vec<u8>) -> OracleMessage {
+        let mut message = message(feed_id, 0, 0, value);
+        message.attestation = Some(Attestation { timestamp, signature });
+        message
+    }
+
+    fn set_provider(feed_id: FeedId, key: ProviderKey) {
+        let provider = Provider { key, signature_path: b"signature".to_vec(), timestamp_path: b"timestamp".to_vec() };
+        assert_ok!(Oracle::set_provider(Origin::signed(OWNER), feed_id, Some(provider)));
+    }
+
+    fn signed_message(feed_id: FeedId, key: &ProviderKey, value: u128, timestamp: u64) -> Vec<u8> {
+        attestation_message(key, feed_id, b"BTC/USD", Price::new(value, 2).unwrap(), timestamp)
+    }
+
+    #[test]
+    fn encodes_ethereum_signed_messages() {
+        let word = |tail: &[u8]| [vec![0; 32 - tail.len()], tail.to_vec()].concat();
+        let encoded = [
+            word(&[3]),
+            word(&[0x80]),
+            word(&[100]),
+            word(&[0x03, 0xe8]),
+            word(&[7]),
+            [b"BTC/USD".to_vec(), vec![0; 25]].concat(),
+        ].concat();
+        assert_eq!(abi_encode(3, b"BTC/USD", Price::new(100, 2).unwrap(), 1_000), encoded);
+
+        let key = ProviderKey::Secp256k1(H160::zero());
+        let message = attestation_message(&key, 3, b"BTC/USD", Price::new(100, 2).unwrap(), 1_000);
+        assert_eq!(&message[..28], &b"\x19Ethereum Signed Message:\n32"[..]);
+        assert_eq!(&message[28..], &runtime_io::keccak_256(&encoded)[..]);
+    }
+
+    #[test]
+    fn checks_ed25519_attestations() {
+        with_externalities(&mut new_test_ext(), || {
+            let feed_id = register_feed();
+            let pair = ed25519::Pair::from_seed(&[1; 32]);
+            let key = ProviderKey::Ed25519(pair.public());
+            set_provider(feed_id, key.clone());
+            timestamp::Module::<Test>::set_timestamp(1_000_000);
+            let feed = Oracle::oracles(feed_id).unwrap();
+            let sign = |value, timestamp| pair.sign(&signed_message(feed_id, &key, value, timestamp)).0.to_vec();
+            let check = |value, timestamp, signed_value| {
+                Oracle::check_attestation(&feed, &attested(feed_id, value, timestamp, sign(signed_value, timestamp)))
+            };
+
+            assert_ok!(check(100, 1_000, 100));
+            assert_ok!(check(100, 1_030, 100));
+            assert_eq!(check(101, 1_000, 100), Err(Error::InvalidAttestation));
+            assert_eq!(check(100, 939, 100), Err(Error::StaleAttestation));
+            assert_eq!(check(100, 1_031, 100), Err(Error::FutureAttestation));
+            assert_eq!(
+                Oracle::check_attestation(&feed, &attested(feed_id, 100, 1_000, vec![0; 63])),
+                Err(Error::InvalidAttestation)
+            );
+            assert_eq!(Oracle::check_attestation(&feed, &message(feed_id, 0, 0, 100)), Err(Error::MissingAttestation));
+
+            // The same provider's price for a feed with the same description is not accepted.
+            let other_feed = register_feed();
+            set_provider(other_feed, key.clone());
+            let other = Oracle::oracles(other_feed).unwrap();
+            assert_eq!(
+                Oracle::check_attestation(&other, &attested(other_feed, 100, 1_000, sign(100, 1_000))),
+                Err(Error::InvalidAttestation)
+            );
+        });
+    }
+
+    #[test]
+    fn checks_secp256k1_attestations() {
+        with_externalities(&mut new_test_ext(), || {
+            let feed_id = register_feed();
+            let secret = secp256k1::SecretKey::parse(&[1; 32]).unwrap();
+            let public = secp256k1::PublicKey::from_secret_key(&secret).serialize();
+            // The Ethereum address, from the key without its format prefix.
+            let address = H160::from_slice(&runtime_io::keccak_256(&public[1..])[12..]);
+            let key = ProviderKey::Secp256k1(address);
+            set_provider(feed_id, key.clone());
+            timestamp::Module::<Test>::set_timestamp(1_000_000);
+            let feed = Oracle::oracles(feed_id).unwrap();
+            let sign = |value, timestamp, recovery_offset: u8| {
+                let hash = runtime_io::keccak_256(&signed_message(feed_id, &key, value, timestamp));
+                let hash = secp256k1::Message::parse(&hash);
+                let (signature, recovery_id) = secp256k1::sign(&hash, &secret);
+                let mut signature = signature.serialize().to_vec();
+                signature.push(recovery_id.serialize() + recovery_offset);
+                signature
+            };
+
+            // Ethereum signers add 27 to the recovery id, both forms are accepted.
+            assert_ok!(Oracle::check_attestation(&feed, &attested(feed_id, 100, 1_000, sign(100, 1_000, 0))));
+            assert_ok!(Oracle::check_attestation(&feed, &attested(feed_id, 100, 1_000, sign(100, 1_000, 27))));
+            assert_eq!(
+                Oracle::check_attestation(&feed, &attested(feed_id, 101, 1_000, sign(100, 1_000, 0))),
+                Err(Error::InvalidAttestation)
+            );
+            assert_eq!(
+                Oracle::check_attestation(&feed, &attested(feed_id, 100, 1_031, sign(100, 1_031, 0))),
+                Err(Error::FutureAttestation)
+            );
+            let mut truncated = sign(100, 1_000, 0);
+            truncated.pop();
+            assert_eq!(
+                Oracle::check_attestation(&feed, &attested(feed_id, 100, 1_000, truncated)),
+                Err(Error::InvalidAttestation)
+            );
+        });
+    }
+
+    #[test]
+    fn commit_reveal_round_reveals_once_the_quorum_committed() {
+        with_externalities(&mut new_test_ext(), || {
+            let plain_feed = register_feed();
+            assert_noop!(commit(plain_feed, 0, 0, 100), Error::NotCommitReveal);
+            // Switching to commit-reveal starts the round over.
+            let feed_id = commit_reveal_feed();
+            assert_noop!(commit(feed_id, 0, 0, 100), Error::WrongRound);
+
+            assert_ok!(commit(feed_id, 1, 0, 100));
+            assert_noop!(commit(feed_id, 1, 0, 100), Error::AlreadySubmitted);
+            assert_noop!(reveal(feed_id, 1, 0, 100), Error::CommitPhase);
+            assert!(!Oracle::current_round(feed_id).revealing);
+
+            // The quorum of two switches the round to reveals, later commitments are kept apart.
+            assert_ok!(commit(feed_id, 1, 1, 102));
+            assert!(Oracle::current_round(feed_id).revealing);
+            assert_eq!(Oracle::revealing_feeds(), vec![feed_id]);
+            assert_ok!(commit(feed_id, 1, 2, 104));
+            let round = Oracle::current_round(feed_id);
+            assert_eq!((round.commits.len(), round.late_commits.len()), (2, 1));
+
+            assert_ok!(reveal(feed_id, 1, 0, 100));
+            assert_eq!(Oracle::current_round(feed_id).id, 1);
+            // A late commitment's reveal completes the quorum in place of the authority that has not revealed.
+            assert_ok!(reveal(feed_id, 1, 2, 104));
+            assert_eq!(Oracle::current_round(feed_id).id, 2);
+            assert_eq!(Oracle::results(feed_id).latest, Price::new(102, 2).unwrap());
+            assert!(Oracle::revealing_feeds().is_empty());
+            assert_ok!(reveal(feed_id, 1, 1, 102));
+        });
+    }
+
+    #[test]
+    fn reveals_must_match_the_commitment() {
+        with_externalities(&mut new_test_ext(), || {
+            let feed_id = commit_reveal_feed();
+            assert_ok!(commit(feed_id, 1, 0, 100));
+            assert_ok!(commit(feed_id, 1, 1, 100));
+
+            assert_noop!(reveal(feed_id, 1, 0, 101), Error::CommitmentMismatch);
+            assert_noop!(report(feed_id, 1, 0, 100), Error::CommitmentMismatch);
+            assert_noop!(reveal(feed_id, 1, 3, 100), Error::NoCommitment);
+            assert_ok!(reveal(feed_id, 1, 0, 100));
+        });
+    }
+
+    #[test]
+    fn unrevealed_round_starts_over_after_the_reveal_period() {
+        with_externalities(&mut new_test_ext(), || {
+            let feed_id = commit_reveal_feed();
+            assert_ok!(commit(feed_id, 1, 0, 100));
+            assert_ok!(commit(feed_id, 1, 1, 100));
+            assert_ok!(reveal(feed_id, 1, 0, 100));
+
+            Oracle::on_initialize(5);
+            assert_eq!(Oracle::current_round(feed_id).id, 1);
+            Oracle::on_initialize(6);
+            let round = Oracle::current_round(feed_id);
+            assert_eq!((round.id, round.revealing, round.submissions.len()), (2, false, 0));
+            assert!(Oracle::revealing_feeds().is_empty());
+            assert_noop!(reveal(feed_id, 1, 1, 100), Error::WrongRound);
+            // The authority that committed without revealing missed the round, the one that revealed did not.
+            let missed = ReporterRecord { rounds: 1, submissions: 0, outliers: 0 };
+            assert_eq!(Oracle::reporter_records(), vec![(2, missed)]);
+
+            assert_ok!(commit(feed_id, 2, 1, 100));
+            assert_eq!(Oracle::rounds(feed_id).commits.len(), 1);
+        });
+    }
+
+    #[test]
+    fn revealing_round_is_abandoned_when_the_session_ends() {
+        with_externalities(&mut new_test_ext(), || {
+            let feed_id = commit_reveal_feed();
+            assert_ok!(commit(feed_id, 1, 0, 100));
+            assert_ok!(commit(feed_id, 1, 1, 100));
+            assert_ok!(reveal(feed_id, 1, 1, 100));
+
+            session::Module::<Test>::rotate_session();
+            assert!(Oracle::revealing_feeds().is_empty());
+            assert_eq!(Oracle::reveal_deadline(feed_id), None);
+            let round = Oracle::current_round(feed_id);
+            assert_eq!((round.id, round.session, round.revealing), (2, 1, false));
+            let missed = ReporterRecord { rounds: 1, submissions: 0, outliers: 0 };
+            assert_eq!(Oracle::reporter_records(), vec![(1, missed)]);
+        });
+    }
+
+    fn offchain_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
+        let mut ext = new_test_ext();
+        let (offchain, _state) = TestOffchainExt::new();
+        ext.set_offchain_externalities(offchain);
+        ext
+    }
+
+    /// The block and round of this node's last submission to `feed_id`.
+    fn last_submission(feed_id: FeedId) -> Option<(u64, RoundId)> {
+        runtime_io::local_storage_get(StorageKind::PERSISTENT, &local_key(SUBMISSION_KEY_PREFIX, feed_id))
+            .and_then(|raw| LastSubmission::<u64>::decode(&mut &raw[..]).ok())
+            .map(|last| (last.block, last.round))
+    }
+
+    #[test]
+    fn submissions_wait_for_the_interval_a_deviation_or_an_open_round() {
+        with_externalities(&mut offchain_test_ext(), || {
+            let feed_id = <NextFreeId>::get();
+            assert_ok!(Oracle::register_new_oracle(
+                Origin::signed(OWNER),
+                b"BTC/USD".to_vec(),
+                b"data.price".to_vec(),
+                2,
+                5,
+                Perbill::from_percent(1),
+                60_000,
+                Perbill::from_percent(50),
+            ));
+            let feed = Oracle::oracles(feed_id).unwrap();
+            let submit_at = |block, value| {
+                assert_ok!(Oracle::submit_if_due(feed_id, &feed, block, Price::new(value, 2).unwrap(), None));
+                last_submission(feed_id)
+            };
+
+            // The first price always deviates, as there is no aggregate yet.
+            assert_eq!(submit_at(1, 100), Some((1, 0)));
+            // No second submission to the same round before the interval passed.
+            assert_eq!(submit_at(2, 100), Some((1, 0)));
+
+            assert_ok!(report(feed_id, 0, 0, 100));
+            assert_ok!(report(feed_id, 0, 1, 100));
+            // A new round is only opened once the interval passed or the price moved.
+            assert_eq!(submit_at(3, 100), Some((1, 0)));
+            assert_eq!(submit_at(3, 102), Some((3, 1)));
+
+            assert_ok!(report(feed_id, 1, 0, 100));
+            assert_ok!(report(feed_id, 1, 1, 100));
+            assert_eq!(submit_at(4, 100), Some((3, 1)));
+            // Rounds other authorities opened are always joined.
+            assert_ok!(report(feed_id, 2, 2, 100));
+            assert_eq!(submit_at(4, 100), Some((4, 2)));
+
+            assert_ok!(report(feed_id, 2, 0, 100));
+            assert_eq!(submit_at(8, 100), Some((4, 2)));
+            assert_eq!(submit_at(9, 100), Some((9, 3)));
+            // A submission that did not make it into the round is repeated once the interval passed.
+            assert_eq!(submit_at(13, 100), Some((9, 3)));
+            assert_eq!(submit_at(14, 100), Some((14, 3)));
+        });
+    }
+
+    #[test]
+    fn worker_lock_excludes_overlapping_runs_until_it_expires() {
+        with_externalities(&mut offchain_test_ext(), || {
+            let first = Oracle::acquire_lock(0, 1).unwrap();
+            assert_eq!(Oracle::acquire_lock(0, 5), None);
+            // Locks are per feed.
+            assert!(Oracle::acquire_lock(1, 5).is_some());
+
+            // An abandoned lock expires after `LOCK_PERIOD` blocks.
+            let second = Oracle::acquire_lock(0, 11).unwrap();
+            // The expired run cannot release the lock another run took since.
+            Oracle::release_lock(0, first);
+            assert_eq!(Oracle::acquire_lock(0, 12), None);
+
+            Oracle::release_lock(0, second);
+            assert!(Oracle::acquire_lock(0, 12).is_some());
+        });
+    }
+
+    #[test]
+    fn rounds_are_spaced_and_history_kept_for_its_period() {
+        with_externalities(&mut new_test_ext(), || {
+            MIN_ROUND_SPACING.with(|v| *v.borrow_mut() = 1_000);
+            let feed_id = register_feed();
+            let finalize_round = |round| {
+                assert_ok!(report(feed_id, round, 0, 100));
+                assert_ok!(report(feed_id, round, 1, 100));
+            };
+
+            timestamp::Module::<Test>::set_timestamp(1_000);
+            finalize_round(0);
+            timestamp::Module::<Test>::set_timestamp(1_999);
+            assert_noop!(report(feed_id, 1, 0, 100), Error::RoundTooSoon);
+            timestamp::Module::<Test>::set_timestamp(2_000);
+            finalize_round(1);
+
+            timestamp::Module::<Test>::set_timestamp(30_000);
+            finalize_round(2);
+
+            // The aggregate of 2_000 held at the start of the period and is kept, the one before is not.
+            timestamp::Module::<Test>::set_timestamp(62_500);
+            finalize_round(3);
+            let history = Oracle::history(feed_id);
+            let timestamps = history.iter().map(|observation| observation.timestamp).collect::<Vec<_>>();
+            assert_eq!(timestamps, vec![2_000, 30_000, 62_500]);
+            assert_eq!(<Oracle as OracleProvider>::twap(feed_id, 60_000), Some(Price::new(100, 2).unwrap()));
+        });
+    }
+
+    #[test]
+    fn commitment_binds_value_salt_and_authority() {
+        let price = Price::new(4200, 2).unwrap();
+        let salt = H256::repeat_byte(1);
+        let committed = commitment(0, 3, 1, price, &salt);
+
+        assert_eq!(commitment(0, 3, 1, price, &salt), committed);
+        assert_ne!(commitment(0, 3, 1, Price::new(4201, 2).unwrap(), &salt), committed);
+        assert_ne!(commitment(0, 3, 1, price, &H256::repeat_byte(2)), committed);
+        // Another authority cannot adopt the commitment as its own.
+        assert_ne!(commitment(0, 3, 2, price, &salt), committed);
+        assert_ne!(commitment(0, 4, 1, price, &salt), committed);
     }
 }
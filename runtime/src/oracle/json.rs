@@ -0,0 +1,234 @@
+//! Minimal JSON reading for the oracle's offchain worker.
+//!
+//! Only what a feed needs is supported: following a dotted path such as `data.price` or `result.0.last` through a
+//! response, and turning the decimal found there into a `Price` with the feed's decimals. Object keys are
+//! compared as written in the response, so keys containing escape sequences cannot be addressed.
+
+use rstd::prelude::*;
+use tiedye_primitives::Price;
+
+/// How deeply nested values are skipped before the response is rejected.
+const MAX_DEPTH: usize = 32;
+
+/// The number or string found at `path` in `json`, without quotes. An empty path selects the whole document.
+pub fn extract<'a>(json: &'a [u8], path: &[u8]) -> Option<&'a [u8]> {
+    let mut parser = Parser { input: json, pos: 0 };
+
+    if !path.is_empty() {
+        for segment in path.split(|b| *b == b'.') {
+            parser.enter(segment)?;
+        }
+    }
+
+    parser.skip_whitespace();
+    match parser.peek()? {
+        b'"' => parser.string(),
+        _ => parser.number(),
+    }
+}
+
+/// Parses a non-negative decimal such as `65000.123` into a price with `decimals` decimals, truncating any
+/// further digits. Exponents are not supported. `None` if `decimals` exceeds `Price::MAX_DECIMALS`.
+pub fn parse_decimal(number: &[u8], decimals: u8) -> Option<Price> {
+    if decimals > Price::MAX_DECIMALS {
+        return None;
+    }
+    let mut parts = number.splitn(2, |b| *b == b'.');
+    let integer = parts.next()?;
+    let fraction = parts.next().unwrap_or(&[]);
+    if integer.is_empty() || !integer.iter().chain(fraction).all(u8::is_ascii_digit) {
+        return None;
+    }
+
+    let padded = fraction.iter().chain(rstd::iter::repeat(&b'0')).take(decimals as usize);
+    let mut value: u128 = 0;
+    for digit in integer.iter().chain(padded) {
+        value = value.checked_mul(10)?.checked_add((digit - b'0') as u128)?;
+    }
+    Price::new(value, decimals)
+}
+
+/// Decodes a hex string, with or without a `0x` prefix.
+pub fn decode_hex(hex: &[u8]) -> Option<Vec<u8>> {
+    let hex = if hex.starts_with(b"0x") { &hex[2..] } else { hex };
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    hex.chunks(2).map(|pair| Some(nibble(pair[0])? << 4 | nibble(pair[1])?)).collect()
+}
+
+fn nibble(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        _ => None,
+    }
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).cloned()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Option<()> {
+        self.skip_whitespace();
+        if self.peek()? != byte {
+            return None;
+        }
+        self.pos += 1;
+        Some(())
+    }
+
+    /// Moves into the member `segment` of the object, or the element at index `segment` of the array, at the
+    /// current position.
+    fn enter(&mut self, segment: &[u8]) -> Option<()> {
+        self.skip_whitespace();
+        match self.peek()? {
+            b'{' => {
+                self.pos += 1;
+                loop {
+                    let key = self.string()?;
+                    self.expect(b':')?;
+                    if key == segment {
+                        return Some(());
+                    }
+                    self.skip_value(0)?;
+                    self.expect(b',')?;
+                }
+            }
+            b'[' => {
+                self.pos += 1;
+                let index = rstd::str::from_utf8(segment).ok()?.parse::<usize>().ok()?;
+                for _ in 0..index {
+                    self.skip_value(0)?;
+                    self.expect(b',')?;
+                }
+                Some(())
+            }
+            _ => None,
+        }
+    }
+
+    /// The raw contents of the string at the current position.
+    fn string(&mut self) -> Option<&'a [u8]> {
+        self.expect(b'"')?;
+        let start = self.pos;
+        loop {
+            match self.peek()? {
+                b'"' => break,
+                b'\\' => self.pos += 2,
+                _ => self.pos += 1,
+            }
+        }
+        let contents = self.input.get(start..self.pos)?;
+        self.pos += 1;
+        Some(contents)
+    }
+
+    /// The number, or `true`, `false` or `null` literal, at the current position.
+    fn number(&mut self) -> Option<&'a [u8]> {
+        self.skip_whitespace();
+        let start = self.pos;
+        while let Some(b'0'..=b'9') | Some(b'a'..=b'z') | Some(b'E') | Some(b'-') | Some(b'+') | Some(b'.') =
+            self.peek()
+        {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return None;
+        }
+        Some(&self.input[start..self.pos])
+    }
+
+    fn skip_value(&mut self, depth: usize) -> Option<()> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+
+        self.skip_whitespace();
+        let (open, close) = match self.peek()? {
+            b'"' => return self.string().map(|_| ()),
+            b'{' => (b'{', b'}'),
+            b'[' => (b'[', b']'),
+            _ => return self.number().map(|_| ()),
+        };
+
+        self.pos += 1;
+        self.skip_whitespace();
+        if self.peek()? == close {
+            self.pos += 1;
+            return Some(());
+        }
+        loop {
+            if open == b'{' {
+                self.string()?;
+                self.expect(b':')?;
+            }
+            self.skip_value(depth + 1)?;
+            self.skip_whitespace();
+            match self.peek()? {
+                b',' => self.pos += 1,
+                byte if byte == close => {
+                    self.pos += 1;
+                    return Some(());
+                }
+                _ => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESPONSE: &[u8] = br#"{
+        "status": "ok",
+        "meta": { "pairs": [1, 2, {"nested": null}], "escaped": "a \"quoted\" word" },
+        "data": { "symbol": "BTC/USD", "price": "65000.12345678", "volume": 1234.5 },
+        "result": [ { "last": 0.00001234 } ]
+    }"#;
+
+    #[test]
+    fn follows_object_and_array_paths() {
+        assert_eq!(extract(RESPONSE, b"data.price"), Some(&b"65000.12345678"[..]));
+        assert_eq!(extract(RESPONSE, b"data.volume"), Some(&b"1234.5"[..]));
+        assert_eq!(extract(RESPONSE, b"result.0.last"), Some(&b"0.00001234"[..]));
+        assert_eq!(extract(b" 42 ", b""), Some(&b"42"[..]));
+        assert_eq!(extract(RESPONSE, b"data.missing"), None);
+        assert_eq!(extract(RESPONSE, b"result.1.last"), None);
+        assert_eq!(extract(b"{\"data\": ", b"data.price"), None);
+    }
+
+    #[test]
+    fn scales_decimals() {
+        assert_eq!(parse_decimal(b"65000.12345678", 8), Some(Price::new(6_500_012_345_678, 8).unwrap()));
+        assert_eq!(parse_decimal(b"65000.12345678", 2), Some(Price::new(6_500_012, 2).unwrap()));
+        assert_eq!(parse_decimal(b"0.00001234", 8), Some(Price::new(1_234, 8).unwrap()));
+        assert_eq!(parse_decimal(b"42", 3), Some(Price::new(42_000, 3).unwrap()));
+        assert_eq!(parse_decimal(b"1.5", 0), Some(Price::new(1, 0).unwrap()));
+        assert_eq!(parse_decimal(b"-1.5", 0), None);
+        assert_eq!(parse_decimal(b"1e5", 0), None);
+        assert_eq!(parse_decimal(b"400000000000000000000000000000000000000", 0), None);
+    }
+
+    #[test]
+    fn decodes_hex() {
+        assert_eq!(decode_hex(b"0x00ff1A"), Some(vec![0x00, 0xff, 0x1a]));
+        assert_eq!(decode_hex(b"ab"), Some(vec![0xab]));
+        assert_eq!(decode_hex(b"abc"), None);
+        assert_eq!(decode_hex(b"zz"), None);
+    }
+}
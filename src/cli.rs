@@ -1,13 +1,114 @@
 use crate::service;
+use codec::{Decode, Encode};
 use futures::{future, Future, sync::oneshot};
+use primitives::offchain::OffchainStorage;
 use std::cell::RefCell;
+use std::collections::BTreeMap;
+use structopt::StructOpt;
 use tokio::runtime::Runtime;
 pub use substrate_cli::{VersionInfo, IntoExit, error};
-use substrate_cli::{informant, parse_and_prepare, ParseAndPrepare, NoCustom};
+use substrate_cli::{informant, parse_and_prepare, impl_augment_clap, ParseAndPrepare, NoCustom};
+use substrate_client::backend::Backend;
 use substrate_service::{AbstractService, Roles as ServiceRoles, Configuration};
+use tiedye_runtime::oracle::{self, Endpoint, FeedId};
 use crate::chain_spec;
 use log::info;
 
+/// The offchain database prefix the runtime's `StorageKind::PERSISTENT` local storage lives under. Substrate's
+/// offchain `Api` keeps it in a private `STORAGE_PREFIX` constant (`core/offchain/src/api.rs` at the pinned
+/// revision), so it has to be checked whenever Substrate is upgraded.
+const OFFCHAIN_STORAGE_PREFIX: &[u8] = b"storage";
+
+/// Where this node's offchain worker fetches the oracle feeds from.
+#[derive(Debug, Clone, StructOpt)]
+pub struct OracleArgs {
+	/// Fetch a feed from the given URL, as `<feed id>=<url>`. May be repeated.
+	#[structopt(long = "oracle-endpoint", value_name = "FEED=URL")]
+	endpoints: Vec<String>,
+
+	/// Send a header when fetching a feed, as `<feed id>=<name>:<value>`. May be repeated.
+	#[structopt(long = "oracle-header", value_name = "FEED=NAME:VALUE")]
+	headers: Vec<String>,
+}
+
+impl_augment_clap!(OracleArgs);
+
+impl OracleArgs {
+	/// The endpoints given on the command line, by feed.
+	fn endpoints(&self) -> Result<BTreeMap<FeedId, Endpoint>, String> {
+		let mut endpoints = BTreeMap::new();
+		for arg in &self.endpoints {
+			let (feed_id, url) = split_feed(arg)?;
+			endpoints.insert(feed_id, Endpoint { url: url.as_bytes().to_vec(), headers: Vec::new() });
+		}
+
+		for arg in &self.headers {
+			let (feed_id, header) = split_feed(arg)?;
+			let mut parts = header.splitn(2, ':');
+			let (name, value) = match (parts.next(), parts.next()) {
+				(Some(name), Some(value)) if !name.is_empty() => (name.trim(), value.trim()),
+				_ => return Err(format!("Invalid oracle header `{}`, expected `FEED=NAME:VALUE`", arg)),
+			};
+			endpoints.get_mut(&feed_id)
+				.ok_or_else(|| format!("Oracle header `{}` given for feed {} without an endpoint", arg, feed_id))?
+				.headers
+				.push((name.as_bytes().to_vec(), value.as_bytes().to_vec()));
+		}
+
+		Ok(endpoints)
+	}
+}
+
+/// Splits a `<feed id>=<rest>` argument.
+fn split_feed(arg: &str) -> Result<(FeedId, &str), String> {
+	let mut parts = arg.splitn(2, '=');
+	match (parts.next().map(str::parse), parts.next()) {
+		(Some(Ok(feed_id)), Some(rest)) => Ok((feed_id, rest)),
+		_ => Err(format!("Invalid oracle argument `{}`, expected the feed id followed by `=`", arg)),
+	}
+}
+
+/// Writes the configured endpoints to the offchain local storage the oracle's offchain worker reads them from,
+/// clearing those configured on an earlier run but not on this one.
+fn store_oracle_endpoints<S: AbstractService>(service: &S, args: &OracleArgs) -> Result<(), String> {
+	let endpoints = args.endpoints()?;
+	let mut storage = match service.client().backend().offchain_storage() {
+		Some(storage) => storage,
+		None if endpoints.is_empty() => return Ok(()),
+		None => return Err("Oracle endpoints require offchain storage".to_string()),
+	};
+
+	for (feed_id, endpoint) in &endpoints {
+		info!("Oracle feed {} fetched from {}", feed_id, String::from_utf8_lossy(&endpoint.url));
+	}
+	let previous = storage.get(OFFCHAIN_STORAGE_PREFIX, oracle::CONFIGURED_FEEDS_KEY);
+	for (key, value) in endpoint_writes(previous, &endpoints) {
+		storage.set(OFFCHAIN_STORAGE_PREFIX, &key, &value);
+	}
+
+	Ok(())
+}
+
+/// The local storage writes configuring `endpoints`, given the feeds configured before as stored under
+/// `oracle::CONFIGURED_FEEDS_KEY`. Earlier endpoints that are no longer configured are cleared, or the worker would
+/// keep querying them with their old headers.
+fn endpoint_writes(previous: Option<Vec<u8>>, endpoints: &BTreeMap<FeedId, Endpoint>) -> Vec<(Vec<u8>, Vec<u8>)> {
+	let previous = previous
+		.and_then(|raw| Vec::<FeedId>::decode(&mut &raw[..]).ok())
+		.unwrap_or_default();
+
+	let cleared = previous.into_iter()
+		.filter(|feed_id| !endpoints.contains_key(feed_id))
+		.map(|feed_id| (oracle::endpoint_key(feed_id), Vec::new()));
+	let configured = endpoints.iter()
+		.map(|(feed_id, endpoint)| (oracle::endpoint_key(*feed_id), endpoint.encode()));
+	let feeds = endpoints.keys().cloned().collect::<Vec<_>>();
+
+	cleared.chain(configured)
+		.chain(Some((oracle::CONFIGURED_FEEDS_KEY.to_vec(), feeds.encode())))
+		.collect()
+}
+
 /// Parse command line arguments into service configuration.
 pub fn run<I, T, E>(args: I, exit: E, version: VersionInfo) -> error::Result<()> where
 	I: IntoIterator<Item = T>,
@@ -15,9 +116,9 @@ pub fn run<I, T, E>(args: I, exit: E, version: VersionInfo) -> error::Result<()>
 	E: IntoExit,
 {
 	type Config<T> = Configuration<(), T>;
-	match parse_and_prepare::<NoCustom, NoCustom, _>(&version, "tiedye-node", args) {
+	match parse_and_prepare::<NoCustom, OracleArgs, _>(&version, "tiedye-node", args) {
 		ParseAndPrepare::Run(cmd) => cmd.run(load_spec, exit,
-		|exit, _cli_args, _custom_args, config: Config<_>| {
+		|exit, _cli_args, oracle_args: OracleArgs, config: Config<_>| {
 			info!("{}", version.name);
 			info!("  version {}", config.full_version());
 			info!("  by {}, 2019", version.author);
@@ -31,11 +132,11 @@ pub fn run<I, T, E>(args: I, exit: E, version: VersionInfo) -> error::Result<()>
 				 	service::new_light(config).map_err(|e| format!("{:?}", e))?,
 					exit
 				),
-				_ => run_until_exit(
-					runtime,
-					service::new_full(config).map_err(|e| format!("{:?}", e))?,
-					exit
-				),
+				_ => {
+					let service = service::new_full(config).map_err(|e| format!("{:?}", e))?;
+					store_oracle_endpoints(&service, &oracle_args)?;
+					run_until_exit(runtime, service, exit)
+				},
 			}.map_err(|e| format!("{:?}", e))
 		}),
 		ParseAndPrepare::BuildSpec(cmd) => cmd.run(load_spec),
@@ -111,3 +212,74 @@ impl IntoExit for Exit {
 		exit.map_err(drop)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn args(endpoints: &[&str], headers: &[&str]) -> OracleArgs {
+		OracleArgs {
+			endpoints: endpoints.iter().map(|arg| arg.to_string()).collect(),
+			headers: headers.iter().map(|arg| arg.to_string()).collect(),
+		}
+	}
+
+	fn endpoint(url: &str, headers: &[(&str, &str)]) -> Endpoint {
+		Endpoint {
+			url: url.as_bytes().to_vec(),
+			headers: headers.iter().map(|(name, value)| (name.as_bytes().to_vec(), value.as_bytes().to_vec())).collect(),
+		}
+	}
+
+	#[test]
+	fn splits_feed_arguments() {
+		assert_eq!(split_feed("3=https://example.com/?a=b"), Ok((3, "https://example.com/?a=b")));
+		assert_eq!(split_feed("0="), Ok((0, "")));
+		assert!(split_feed("https://example.com").is_err());
+		assert!(split_feed("btc=https://example.com").is_err());
+		assert!(split_feed("-1=https://example.com").is_err());
+	}
+
+	#[test]
+	fn collects_endpoints_with_their_headers() {
+		let endpoints = args(
+			&["0=https://a.example", "1=https://b.example"],
+			&["1=X-Api-Key: secret:part", "1=Accept:application/json"],
+		).endpoints().unwrap();
+
+		assert_eq!(endpoints.len(), 2);
+		assert_eq!(endpoints[&0], endpoint("https://a.example", &[]));
+		assert_eq!(
+			endpoints[&1],
+			endpoint("https://b.example", &[("X-Api-Key", "secret:part"), ("Accept", "application/json")]),
+		);
+	}
+
+	#[test]
+	fn rejects_malformed_headers() {
+		assert!(args(&["0=https://a.example"], &["0=no-value"]).endpoints().is_err());
+		assert!(args(&["0=https://a.example"], &["0=:value"]).endpoints().is_err());
+		// A header needs an endpoint for its feed.
+		assert!(args(&["0=https://a.example"], &["1=Accept:application/json"]).endpoints().is_err());
+	}
+
+	#[test]
+	fn clears_endpoints_no_longer_configured() {
+		let endpoints = args(&["1=https://b.example"], &[]).endpoints().unwrap();
+		let writes = endpoint_writes(Some(vec![0 as FeedId, 1].encode()), &endpoints);
+
+		assert_eq!(writes, vec![
+			(oracle::endpoint_key(0), Vec::new()),
+			(oracle::endpoint_key(1), endpoint("https://b.example", &[]).encode()),
+			(oracle::CONFIGURED_FEEDS_KEY.to_vec(), vec![1 as FeedId].encode()),
+		]);
+
+		// Without flags every earlier endpoint is cleared.
+		let writes = endpoint_writes(Some(vec![1 as FeedId].encode()), &BTreeMap::new());
+		assert_eq!(writes, vec![
+			(oracle::endpoint_key(1), Vec::new()),
+			(oracle::CONFIGURED_FEEDS_KEY.to_vec(), Vec::<FeedId>::new().encode()),
+		]);
+		assert_eq!(endpoint_writes(None, &BTreeMap::new()).len(), 1);
+	}
+}